@@ -0,0 +1,83 @@
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum ParseError {
+    #[error("disk map is empty")]
+    Empty,
+
+    #[error("invalid character {ch:?} in disk map")]
+    InvalidDigit {
+        ch: char,
+        #[source_code]
+        src: String,
+        #[label("expected a digit 0-9")]
+        span: SourceSpan,
+    },
+}
+
+/// Parses the dense disk map into `(file block count, free block count)` pairs.
+///
+/// A map of odd length ends with a file that has no free space after it.
+/// Surrounding whitespace (trailing newlines, CRLF) is ignored.
+pub fn parse(input: &str) -> Result<Vec<(u32, u32)>, ParseError> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err(ParseError::Empty);
+    }
+    let offset = input.len() - input.trim_start().len();
+
+    let digits = trimmed
+        .char_indices()
+        .map(|(i, ch)| {
+            ch.to_digit(10).ok_or_else(|| ParseError::InvalidDigit {
+                ch,
+                src: input.to_string(),
+                span: (offset + i, ch.len_utf8()).into(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(digits
+        .chunks(2)
+        .map(|pair| (pair[0], pair.get(1).copied().unwrap_or(0)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("12345", vec![(1, 2), (3, 4), (5, 0)])]
+    #[case("1234", vec![(1, 2), (3, 4)])]
+    #[case("12345\n", vec![(1, 2), (3, 4), (5, 0)])]
+    #[case("12345\r\n\r\n", vec![(1, 2), (3, 4), (5, 0)])]
+    #[case("  90 ", vec![(9, 0)])]
+    fn test_parse(#[case] input: &str, #[case] expected: Vec<(u32, u32)>) {
+        assert_eq!(expected, parse(input).unwrap());
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("\r\n")]
+    fn test_parse_empty(#[case] input: &str) {
+        assert!(matches!(parse(input), Err(ParseError::Empty)));
+    }
+
+    #[rstest]
+    #[case("12x45", 'x', 2)]
+    #[case(" 12\n45", '\n', 3)]
+    #[case("123é", 'é', 3)]
+    fn test_parse_invalid(#[case] input: &str, #[case] expected: char, #[case] at: usize) {
+        match parse(input) {
+            Err(ParseError::InvalidDigit { ch, span, .. }) => {
+                assert_eq!(expected, ch);
+                assert_eq!(at, span.offset());
+            }
+            other => panic!("expected invalid digit error, got {other:?}"),
+        }
+    }
+}
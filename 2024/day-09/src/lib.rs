@@ -1,2 +1,3 @@
+pub mod disk_map;
 pub mod part1;
 pub mod part2;
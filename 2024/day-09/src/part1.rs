@@ -1,23 +1,8 @@
-use itertools::Itertools;
+use crate::disk_map;
 
 #[tracing::instrument]
 pub fn process(input: &str) -> miette::Result<usize> {
-    let mut files = {
-        let mut pair_iter = input
-            .lines()
-            .next()
-            .unwrap()
-            .chars()
-            .map(|ch| {
-                ch.to_digit(10)
-                    .unwrap_or_else(|| panic!("'{}' should be digit", ch))
-            })
-            .tuples();
-
-        let mut files = pair_iter.by_ref().collect_vec();
-        files.extend(pair_iter.into_buffer().map(|used_count| (used_count, 0)));
-        files
-    };
+    let mut files = disk_map::parse(input)?;
 
     let mut checksum = 0;
     let mut block_index = 0;
@@ -59,6 +44,8 @@ pub fn process(input: &str) -> miette::Result<usize> {
 
 #[cfg(test)]
 mod tests {
+    use rstest::rstest;
+
     use super::*;
 
     #[test]
@@ -68,4 +55,20 @@ mod tests {
         assert_eq!(1928, process(input)?);
         Ok(())
     }
+
+    #[rstest]
+    #[case("2333133121414131402\r\n", 1928)]
+    #[case("12345\n", 60)]
+    fn test_process_more_inputs(
+        #[case] input: &str,
+        #[case] expected: usize,
+    ) -> miette::Result<()> {
+        assert_eq!(expected, process(input)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_empty() {
+        assert!(process("\n").is_err());
+    }
 }
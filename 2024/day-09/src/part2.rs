@@ -1,23 +1,16 @@
+use crate::disk_map;
+
 #[tracing::instrument]
 pub fn process(input: &str) -> miette::Result<usize> {
-    let (mut files, mut frees, _) = input
-        .lines()
-        .next()
-        .unwrap()
-        .chars()
-        .map(|ch| {
-            ch.to_digit(10)
-                .unwrap_or_else(|| panic!("'{}' should be digit", ch))
-        })
-        .enumerate()
-        .fold(
-            (Vec::new(), Vec::new(), 0),
-            |(mut files, mut frees, block_index), (i, block_count)| {
-                if i % 2 == 0 { &mut files } else { &mut frees }
-                    .push(block_index..block_index + block_count);
-                (files, frees, block_index + block_count)
-            },
-        );
+    let (mut files, mut frees, _) = disk_map::parse(input)?.into_iter().fold(
+        (Vec::new(), Vec::new(), 0),
+        |(mut files, mut frees, block_index), (file_count, free_count)| {
+            let free_index = block_index + file_count;
+            files.push(block_index..free_index);
+            frees.push(free_index..free_index + free_count);
+            (files, frees, free_index + free_count)
+        },
+    );
 
     for file in files.iter_mut().rev() {
         for free in frees.iter_mut() {
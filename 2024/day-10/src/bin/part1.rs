@@ -1,12 +1,39 @@
-use day_10::part1::process;
-use miette::Context;
+use std::borrow::Cow;
+
+use day_10::{
+    part1::{process, score},
+    trails,
+};
+use miette::{Context, IntoDiagnostic};
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
 
 #[tracing::instrument]
 fn main() -> miette::Result<()> {
     tracing_subscriber::fmt::init();
 
-    let file = include_str!("../../input.txt");
-    let result = process(file).context("process part 1")?;
+    // `--input <file>` reads another map, and `--example` uses the worked example
+    let file = match arg_value("--input") {
+        Some(path) => Cow::Owned(
+            std::fs::read_to_string(&path)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("read map from {path}"))?,
+        ),
+        None if std::env::args().any(|arg| arg == "--example") => Cow::Borrowed(trails::EXAMPLE),
+        None => Cow::Borrowed(include_str!("../../input.txt")),
+    };
+
+    // `--breakdown` also lists every trailhead on stderr
+    if std::env::args().any(|arg| arg == "--breakdown") {
+        let trailheads = trails::trailheads(&file)?;
+        eprint!("{}", trails::Breakdown(&trailheads));
+        print!("{}", score(&trailheads));
+        return Ok(());
+    }
+
+    let result = process(&file).context("process part 1")?;
     print!("{}", result);
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,68 +1,68 @@
-use std::collections::HashSet;
-
-use glam::IVec2;
-use itertools::Itertools;
-
-fn parse(input: &str) -> (Vec<Vec<u8>>, Vec<IVec2>) {
-    let mut trailheads = Vec::new();
-    (
-        input
-            .lines()
-            .enumerate()
-            .map(|(y, line)| {
-                line.chars()
-                    .map(|c| c.to_digit(10).unwrap() as u8)
-                    .enumerate()
-                    .inspect(|&(x, height)| {
-                        if height == 0 {
-                            trailheads.push(IVec2::new(x as _, y as _));
-                        }
-                    })
-                    .map(|(_, height)| height)
-                    .collect_vec()
-            })
-            .collect_vec(),
-        trailheads,
-    )
-}
+use crate::trails::{self, Trailhead};
 
 #[tracing::instrument]
 pub fn process(input: &str) -> miette::Result<usize> {
-    let (map, trailheads) = parse(input);
-
-    let count = trailheads
-        .iter()
-        .map(|&trailhead| {
-            (1..=9).fold(HashSet::from([trailhead]), |trails, height| {
-                trails
-                    .iter()
-                    .flat_map(|pos| {
-                        [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y]
-                            .iter()
-                            .map(move |dir| pos + dir)
-                            .filter(|&IVec2 { x, y }| {
-                                match (usize::try_from(x), usize::try_from(y)) {
-                                    (Ok(x), Ok(y)) => map
-                                        .get(y)
-                                        .and_then(|r| r.get(x))
-                                        .is_some_and(|&d| d == height),
-                                    _ => false,
-                                }
-                            })
-                    })
-                    .collect()
-            })
-        })
-        .map(|trail| trail.len())
-        .sum();
+    Ok(score(&trails::trailheads(input)?))
+}
 
-    Ok(count)
+/// The sum of the scores of `trailheads`.
+pub fn score(trailheads: &[Trailhead]) -> usize {
+    trailheads.iter().map(|trailhead| trailhead.score).sum()
 }
 
 #[cfg(test)]
 mod tests {
+    use rstest::rstest;
+
     use super::*;
 
+    #[rstest]
+    #[case(
+        "0123
+1234
+8765
+9876
+",
+        1
+    )]
+    #[case(
+        "...0...
+...1...
+...2...
+6543456
+7.....7
+8.....8
+9.....9
+",
+        2
+    )]
+    #[case(
+        "..90..9
+...1.98
+...2..7
+6543456
+765.987
+876....
+987....
+",
+        4
+    )]
+    #[case(
+        "10..9..
+2...8..
+3...7..
+4567654
+...8..3
+...9..2
+.....01
+",
+        3
+    )]
+    fn test_process_small_maps(#[case] input: &str, #[case] expected: usize) -> miette::Result<()> {
+        assert_eq!(expected, process(input)?);
+        Ok(())
+    }
+
     #[test]
     fn test_process() -> miette::Result<()> {
         tracing_subscriber::fmt::init();
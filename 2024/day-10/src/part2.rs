@@ -1,67 +1,116 @@
-
-use glam::IVec2;
-use itertools::Itertools;
-
-fn parse(input: &str) -> (Vec<Vec<u8>>, Vec<IVec2>) {
-    let mut trailheads = Vec::new();
-    (
-        input
-            .lines()
-            .enumerate()
-            .map(|(y, line)| {
-                line.chars()
-                    .map(|c| c.to_digit(10).unwrap() as u8)
-                    .enumerate()
-                    .inspect(|&(x, height)| {
-                        if height == 0 {
-                            trailheads.push(IVec2::new(x as _, y as _));
-                        }
-                    })
-                    .map(|(_, height)| height)
-                    .collect_vec()
-            })
-            .collect_vec(),
-        trailheads,
-    )
-}
+use crate::trails::{MapFormat, Rules, TopoMap, Trailhead};
 
 #[tracing::instrument]
-pub fn process(input: &str) -> miette::Result<usize> {
-    let (map, trailheads) = parse(input);
+pub fn process(input: &str) -> miette::Result<u64> {
+    let map = TopoMap::parse(input, &MapFormat::default())?;
+    let rules = Rules::default();
+    let ratings = map.ratings(&rules);
 
-    let count = trailheads
-        .iter()
-        .map(|&trailhead| {
-            (1..=9).fold(vec![trailhead], |trails, height| {
-                trails
-                    .iter()
-                    .flat_map(|pos| {
-                        [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y]
-                            .iter()
-                            .map(move |dir| pos + dir)
-                            .filter(|&IVec2 { x, y }| {
-                                match (usize::try_from(x), usize::try_from(y)) {
-                                    (Ok(x), Ok(y)) => map
-                                        .get(y)
-                                        .and_then(|r| r.get(x))
-                                        .is_some_and(|&d| d == height),
-                                    _ => false,
-                                }
-                            })
-                    })
-                    .collect()
-            })
-        })
-        .map(|trail| trail.len())
+    let rating = map
+        .trailheads(&rules)
+        .map(|pos| ratings[map.index(pos)])
         .sum();
 
-    Ok(count)
+    Ok(rating)
+}
+
+/// The sum of the ratings of `trailheads`.
+pub fn rating(trailheads: &[Trailhead]) -> u64 {
+    trailheads.iter().map(|trailhead| trailhead.rating).sum()
 }
 
 #[cfg(test)]
 mod tests {
+    use glam::IVec2;
+    use itertools::Itertools;
+    use rstest::rstest;
+
     use super::*;
 
+    fn brute_force(input: &str) -> u64 {
+        let rows = input
+            .lines()
+            .map(|line| line.chars().map(|c| c.to_digit(10)).collect_vec())
+            .collect_vec();
+        let get = |IVec2 { x, y }: IVec2| {
+            usize::try_from(y)
+                .ok()
+                .zip(usize::try_from(x).ok())
+                .and_then(|(y, x)| *rows.get(y)?.get(x)?)
+        };
+
+        (0..rows.len())
+            .cartesian_product(0..rows[0].len())
+            .map(|(y, x)| IVec2::new(x as _, y as _))
+            .filter(|&pos| get(pos) == Some(0))
+            .map(|trailhead| {
+                (1..=9).fold(vec![trailhead], |trails, height| {
+                    trails
+                        .iter()
+                        .flat_map(|pos| {
+                            [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y]
+                                .iter()
+                                .map(move |dir| pos + dir)
+                                .filter(|&next| get(next) == Some(height))
+                        })
+                        .collect()
+                })
+            })
+            .map(|trails| trails.len() as u64)
+            .sum()
+    }
+
+    #[rstest]
+    #[case(
+        ".....0.
+..4321.
+..5..2.
+..6543.
+..7..4.
+..8765.
+..9....
+",
+        3
+    )]
+    #[case(
+        "..90..9
+...1.98
+...2..7
+6543456
+765.987
+876....
+987....
+",
+        13
+    )]
+    #[case(
+        "012345
+123456
+234567
+345678
+4.6789
+56789.
+",
+        227
+    )]
+    #[case(
+        "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732
+",
+        81
+    )]
+    fn test_process_small_maps(#[case] input: &str, #[case] expected: u64) -> miette::Result<()> {
+        assert_eq!(expected, process(input)?);
+        assert_eq!(brute_force(input), process(input)?);
+        Ok(())
+    }
+
     #[test]
     fn test_process() -> miette::Result<()> {
         tracing_subscriber::fmt::init();
@@ -77,4 +126,15 @@ mod tests {
         assert_eq!(81, process(input)?);
         Ok(())
     }
+
+    #[test]
+    fn test_process_open_plateau() -> miette::Result<()> {
+        // height = min(x + y, 9), so every monotone lattice path from the corner is a trail
+        let input = (0..10)
+            .map(|y| (0..10).map(|x| char::from(b'0' + (x + y).min(9))).join(""))
+            .join("\n");
+        assert_eq!(512, process(&input)?);
+        assert_eq!(brute_force(&input), process(&input)?);
+        Ok(())
+    }
 }
@@ -0,0 +1,584 @@
+use std::{collections::HashMap, fmt, ops::RangeInclusive};
+
+use glam::IVec2;
+use itertools::Itertools;
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+/// The larger worked example of the puzzle, with 9 trailheads.
+pub const EXAMPLE: &str = "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732
+";
+
+const DIRECTIONS: [IVec2; 8] = [
+    IVec2::X,
+    IVec2::Y,
+    IVec2::NEG_X,
+    IVec2::NEG_Y,
+    IVec2::ONE,
+    IVec2::NEG_ONE,
+    IVec2::new(1, -1),
+    IVec2::new(-1, 1),
+];
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum ParseError {
+    #[error("invalid map cell {cell:?}")]
+    InvalidCell {
+        cell: String,
+        #[source_code]
+        src: String,
+        #[label("expected a height or an impassable symbol")]
+        span: SourceSpan,
+    },
+
+    #[error("map row {row} has {len} cells, expected {expected}")]
+    RaggedRow {
+        row: usize,
+        len: usize,
+        expected: usize,
+        #[source_code]
+        src: String,
+        #[label("this row")]
+        span: SourceSpan,
+    },
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("height step {step:?} must be non-empty and exclude 0")]
+#[diagnostic(help(
+    "every step has to move strictly towards the goal, or trails could loop forever"
+))]
+pub struct RulesError {
+    step: RangeInclusive<i64>,
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("cells {width} characters wide in radix {radix} cannot be read")]
+#[diagnostic(help("cells are at least 1 character wide, with digits in a radix from 2 to 36"))]
+pub struct EncodingError {
+    width: usize,
+    radix: u32,
+}
+
+/// How each height is written down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoding {
+    /// Characters per cell, at least 1.
+    width: usize,
+    /// Between 2 and 36, the radixes [`char::to_digit`] supports.
+    radix: u32,
+}
+
+impl Encoding {
+    pub const DECIMAL: Self = Self {
+        width: 1,
+        radix: 10,
+    };
+    pub const HEX: Self = Self {
+        width: 1,
+        radix: 16,
+    };
+
+    pub fn new(width: usize, radix: u32) -> Result<Self, EncodingError> {
+        if width == 0 || !(2..=36).contains(&radix) {
+            return Err(EncodingError { width, radix });
+        }
+
+        Ok(Self { width, radix })
+    }
+}
+
+/// How the map is written down.
+#[derive(Debug, Clone)]
+pub struct MapFormat {
+    /// Symbols for cells that can never be stepped on. A cell made only of these is impassable.
+    pub impassable: Vec<char>,
+    pub encoding: Encoding,
+}
+
+impl Default for MapFormat {
+    fn default() -> Self {
+        Self {
+            impassable: vec!['.'],
+            encoding: Encoding::DECIMAL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Neighbourhood {
+    #[default]
+    Four,
+    Eight,
+}
+
+impl Neighbourhood {
+    fn directions(self) -> &'static [IVec2] {
+        match self {
+            Neighbourhood::Four => &DIRECTIONS[..4],
+            Neighbourhood::Eight => &DIRECTIONS,
+        }
+    }
+}
+
+/// What counts as a hiking trail: it starts at a `start` cell, ends at a `goal` cell, and every
+/// step goes to a neighbour whose height differs by an amount within `step`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+    neighbourhood: Neighbourhood,
+    step: RangeInclusive<i64>,
+    start: u32,
+    goal: u32,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            neighbourhood: Neighbourhood::Four,
+            step: 1..=1,
+            start: 0,
+            goal: 9,
+        }
+    }
+}
+
+impl Rules {
+    /// `step` is either all ascending or all descending, so no trail can revisit a cell.
+    pub fn new(
+        neighbourhood: Neighbourhood,
+        step: RangeInclusive<i64>,
+        start: u32,
+        goal: u32,
+    ) -> Result<Self, RulesError> {
+        if step.is_empty() || step.contains(&0) {
+            return Err(RulesError { step });
+        }
+
+        Ok(Self {
+            neighbourhood,
+            step,
+            start,
+            goal,
+        })
+    }
+
+    fn allows(&self, from: u32, to: u32) -> bool {
+        self.step.contains(&(to as i64 - from as i64))
+    }
+
+    /// Signed distance left to climb (or descend) from `height` to the goal.
+    fn remaining(&self, height: u32) -> i64 {
+        if *self.step.start() > 0 {
+            self.goal as i64 - height as i64
+        } else {
+            height as i64 - self.goal as i64
+        }
+    }
+}
+
+impl MapFormat {
+    /// `Some(None)` for an impassable cell, `None` if the cell is invalid.
+    fn parse_cell(&self, cell: &str) -> Option<Option<u32>> {
+        let Encoding { width, radix } = self.encoding;
+        if cell.chars().count() != width {
+            return None;
+        }
+        if cell.chars().all(|ch| self.impassable.contains(&ch)) {
+            return Some(None);
+        }
+
+        // multi-character cells may be right-aligned with spaces
+        let digits = cell.trim_start_matches(' ');
+        if digits.is_empty() || !digits.chars().all(|ch| ch.is_digit(radix)) {
+            return None;
+        }
+        u32::from_str_radix(digits, radix).ok().map(Some)
+    }
+}
+
+pub(crate) struct TopoMap {
+    /// `None` for impassable cells.
+    heights: Vec<Option<u32>>,
+    size: IVec2,
+}
+
+impl TopoMap {
+    pub(crate) fn parse(input: &str, format: &MapFormat) -> Result<Self, ParseError> {
+        let width = format.encoding.width;
+        let mut heights = Vec::new();
+        let mut size = IVec2::ZERO;
+
+        for (y, line) in input.lines().enumerate() {
+            let line_offset = line.as_ptr() as usize - input.as_ptr() as usize;
+
+            let row_start = heights.len();
+            let cell_starts = line
+                .char_indices()
+                .map(|(i, _)| i)
+                .step_by(width)
+                .collect_vec();
+            let cell_ends = cell_starts.iter().skip(1).copied().chain([line.len()]);
+            for (start, end) in cell_starts.iter().copied().zip(cell_ends) {
+                let cell = &line[start..end];
+                heights.push(
+                    format
+                        .parse_cell(cell)
+                        .ok_or_else(|| ParseError::InvalidCell {
+                            cell: cell.to_string(),
+                            src: input.to_string(),
+                            span: (line_offset + start, cell.len()).into(),
+                        })?,
+                );
+            }
+
+            let len = heights.len() - row_start;
+            if y == 0 {
+                size.x = len as _;
+            } else if len != size.x as usize {
+                return Err(ParseError::RaggedRow {
+                    row: y,
+                    len,
+                    expected: size.x as _,
+                    src: input.to_string(),
+                    span: (line_offset, line.len()).into(),
+                });
+            }
+            size.y += 1;
+        }
+
+        Ok(Self { heights, size })
+    }
+
+    fn get(&self, pos: IVec2) -> Option<u32> {
+        if IVec2::ZERO.cmple(pos).all() && pos.cmplt(self.size).all() {
+            self.heights[self.index(pos)]
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn index(&self, pos: IVec2) -> usize {
+        (pos.y * self.size.x + pos.x) as usize
+    }
+
+    fn pos(&self, index: usize) -> IVec2 {
+        IVec2::new(index as i32 % self.size.x, index as i32 / self.size.x)
+    }
+
+    fn next_steps<'a>(&'a self, pos: IVec2, rules: &'a Rules) -> impl Iterator<Item = IVec2> + 'a {
+        let height = self.get(pos);
+        rules
+            .neighbourhood
+            .directions()
+            .iter()
+            .map(move |dir| pos + dir)
+            .filter(move |&next| {
+                height
+                    .zip(self.get(next))
+                    .is_some_and(|(from, to)| rules.allows(from, to))
+            })
+    }
+
+    pub(crate) fn trailheads<'a>(&'a self, rules: &'a Rules) -> impl Iterator<Item = IVec2> + 'a {
+        (0..self.heights.len())
+            .filter(|&i| self.heights[i] == Some(rules.start))
+            .map(|i| self.pos(i))
+    }
+
+    /// Number of distinct trails from each cell to any goal, indexed like `heights`.
+    pub(crate) fn ratings(&self, rules: &Rules) -> Vec<u64> {
+        // filled from the goal backwards so that every next step is final before it is summed
+        let mut ratings = vec![0u64; self.heights.len()];
+        let cells = (0..self.heights.len())
+            .filter_map(|i| Some((i, rules.remaining(self.heights[i]?))))
+            .sorted_unstable_by_key(|&(_, remaining)| remaining);
+        for (index, remaining) in cells {
+            ratings[index] = match remaining {
+                0 => 1,
+                ..0 => 0,
+                _ => self
+                    .next_steps(self.pos(index), rules)
+                    .map(|next| ratings[self.index(next)])
+                    .sum(),
+            };
+        }
+        ratings
+    }
+
+    /// The goal cells reachable from `start`, in reading order.
+    fn summits_from(&self, start: IVec2, rules: &Rules) -> Vec<IVec2> {
+        let mut visited = vec![false; self.heights.len()];
+        let mut stack = vec![start];
+        let mut summits = Vec::new();
+
+        while let Some(pos) = stack.pop() {
+            if std::mem::replace(&mut visited[self.index(pos)], true) {
+                continue;
+            }
+            match self.get(pos).map(|height| rules.remaining(height)) {
+                Some(0) => summits.push(pos),
+                Some(1..) => stack.extend(self.next_steps(pos, rules)),
+                _ => (),
+            }
+        }
+
+        summits.sort_unstable_by_key(|pos| (pos.y, pos.x));
+        summits
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trailhead {
+    pub pos: IVec2,
+    pub score: usize,
+    pub rating: u64,
+    pub summits: Vec<IVec2>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summit {
+    pub pos: IVec2,
+    pub trailheads: Vec<IVec2>,
+}
+
+/// Every trailhead of the map in reading order, with its score, rating and reachable summits.
+pub fn trailheads(input: &str) -> Result<Vec<Trailhead>, ParseError> {
+    trailheads_with(input, &MapFormat::default(), &Rules::default())
+}
+
+pub fn trailheads_with(
+    input: &str,
+    format: &MapFormat,
+    rules: &Rules,
+) -> Result<Vec<Trailhead>, ParseError> {
+    let map = TopoMap::parse(input, format)?;
+    let ratings = map.ratings(rules);
+
+    let trailheads = map
+        .trailheads(rules)
+        .map(|pos| {
+            let summits = map.summits_from(pos, rules);
+            Trailhead {
+                pos,
+                score: summits.len(),
+                rating: ratings[map.index(pos)],
+                summits,
+            }
+        })
+        .collect();
+
+    Ok(trailheads)
+}
+
+/// Every reachable summit in reading order, with the trailheads that reach it.
+pub fn summits(trailheads: &[Trailhead]) -> Vec<Summit> {
+    let mut summits: HashMap<IVec2, Vec<IVec2>> = HashMap::new();
+    for trailhead in trailheads {
+        for &summit in &trailhead.summits {
+            summits.entry(summit).or_default().push(trailhead.pos);
+        }
+    }
+
+    summits
+        .into_iter()
+        .map(|(pos, trailheads)| Summit { pos, trailheads })
+        .sorted_unstable_by_key(|summit| (summit.pos.y, summit.pos.x))
+        .collect()
+}
+
+/// Human-readable per-trailhead and per-summit listing.
+pub struct Breakdown<'a>(pub &'a [Trailhead]);
+
+impl fmt::Display for Breakdown<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for trailhead in self.0 {
+            writeln!(
+                f,
+                "trailhead {}: score {}, rating {}, summits {}",
+                trailhead.pos,
+                trailhead.score,
+                trailhead.rating,
+                trailhead.summits.iter().join(" "),
+            )?;
+        }
+        for summit in summits(self.0) {
+            writeln!(
+                f,
+                "summit {}: reached from {}",
+                summit.pos,
+                summit.trailheads.iter().join(" "),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trailheads() {
+        let trailheads = trailheads(EXAMPLE).unwrap();
+        assert_eq!(
+            vec![5, 6, 5, 3, 1, 3, 5, 3, 5],
+            trailheads.iter().map(|t| t.score).collect_vec()
+        );
+        assert_eq!(
+            vec![20, 24, 10, 4, 1, 4, 5, 8, 5],
+            trailheads.iter().map(|t| t.rating).collect_vec()
+        );
+        assert_eq!(IVec2::new(2, 0), trailheads[0].pos);
+        assert_eq!(
+            vec![
+                IVec2::new(1, 0),
+                IVec2::new(0, 3),
+                IVec2::new(4, 3),
+                IVec2::new(5, 4),
+                IVec2::new(4, 5),
+            ],
+            trailheads[0].summits
+        );
+    }
+
+    #[test]
+    fn test_summits() {
+        let trailheads = trailheads(EXAMPLE).unwrap();
+        let summits = summits(&trailheads);
+
+        assert_eq!(7, summits.len());
+        assert_eq!(
+            trailheads.iter().map(|t| t.score).sum::<usize>(),
+            summits.iter().map(|s| s.trailheads.len()).sum::<usize>()
+        );
+        for summit in &summits {
+            for pos in &summit.trailheads {
+                let trailhead = trailheads.iter().find(|t| t.pos == *pos).unwrap();
+                assert!(trailhead.summits.contains(&summit.pos));
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_impassable() {
+        let format = MapFormat {
+            impassable: vec!['#', ' '],
+            ..Default::default()
+        };
+        assert!(trailheads("0123\n#654\n87  \n9###\n").is_err());
+
+        let trailheads =
+            trailheads_with("0123\n#654\n87  \n9###\n", &format, &Rules::default()).unwrap();
+        assert_eq!(1, trailheads.len());
+        assert_eq!(1, trailheads[0].score);
+        assert_eq!(vec![IVec2::new(0, 3)], trailheads[0].summits);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        match trailheads("012\r\n3x5\r\n") {
+            Err(ParseError::InvalidCell { cell, span, .. }) => {
+                assert_eq!("x", cell);
+                assert_eq!(6, span.offset());
+            }
+            other => panic!("expected invalid cell error, got {other:?}"),
+        }
+        match trailheads("012\n34\n") {
+            Err(ParseError::RaggedRow { row, len, span, .. }) => {
+                assert_eq!((1, 2), (row, len));
+                assert_eq!(4, span.offset());
+            }
+            other => panic!("expected ragged row error, got {other:?}"),
+        }
+    }
+
+    fn totals(input: &str, format: &MapFormat, rules: &Rules) -> (usize, u64) {
+        let trailheads = trailheads_with(input, format, rules).unwrap();
+        (
+            trailheads.iter().map(|t| t.score).sum(),
+            trailheads.iter().map(|t| t.rating).sum(),
+        )
+    }
+
+    #[test]
+    fn test_descent() {
+        let inverted = EXAMPLE
+            .chars()
+            .map(|ch| match ch.to_digit(10) {
+                Some(d) => char::from_digit(9 - d, 10).unwrap(),
+                None => ch,
+            })
+            .collect::<String>();
+        let rules = Rules::new(Neighbourhood::Four, -1..=-1, 9, 0).unwrap();
+
+        assert_eq!((36, 81), totals(&inverted, &MapFormat::default(), &rules));
+    }
+
+    #[test]
+    fn test_neighbourhood_and_step() {
+        let format = MapFormat::default();
+
+        let diagonal = Rules::new(Neighbourhood::Eight, 1..=1, 0, 1).unwrap();
+        assert_eq!(
+            (0, 0),
+            totals(
+                "0.\n.1\n",
+                &format,
+                &Rules {
+                    goal: 1,
+                    ..Default::default()
+                }
+            )
+        );
+        assert_eq!((1, 1), totals("0.\n.1\n", &format, &diagonal));
+
+        let stride = Rules::new(Neighbourhood::Four, 1..=2, 0, 6).unwrap();
+        assert_eq!((1, 1), totals("0246\n", &format, &stride));
+        assert_eq!((2, 4), totals("0246\n1356\n", &format, &stride));
+    }
+
+    #[test]
+    fn test_tall_encodings() {
+        let hex = MapFormat {
+            encoding: Encoding::HEX,
+            ..Default::default()
+        };
+        let rules = Rules::new(Neighbourhood::Four, 1..=1, 0, 15).unwrap();
+        assert_eq!((1, 1), totals("01234567\nfedcBA98\n", &hex, &rules));
+
+        let wide = MapFormat {
+            encoding: Encoding::new(2, 10).unwrap(),
+            ..Default::default()
+        };
+        let rules = Rules::new(Neighbourhood::Four, 1..=1, 0, 12).unwrap();
+        assert_eq!(
+            (1, 1),
+            totals(" 0 1 2 3\n..10 5 4\n1211 6 7\n..10 9 8\n", &wide, &rules)
+        );
+        assert!(trailheads_with(" 0 1 2 3\n..10 5 4\n1211 6 7\n..10 9 \n", &wide, &rules).is_err());
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(Rules::new(Neighbourhood::Four, 0..=1, 0, 9).is_err());
+        assert!(Rules::new(Neighbourhood::Four, -1..=1, 0, 9).is_err());
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 2..=1;
+        assert!(Rules::new(Neighbourhood::Four, empty, 0, 9).is_err());
+    }
+
+    #[test]
+    fn test_invalid_encodings() {
+        assert!(Encoding::new(0, 10).is_err());
+        assert!(Encoding::new(1, 1).is_err());
+        assert!(Encoding::new(1, 37).is_err());
+        assert_eq!(Encoding::HEX, Encoding::new(1, 16).unwrap());
+    }
+}
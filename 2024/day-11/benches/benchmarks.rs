@@ -1,4 +1,5 @@
 use day_11::*;
+use num_bigint::BigUint;
 
 fn main() {
     // Run registered benchmarks.
@@ -14,3 +15,28 @@ fn part1() {
 fn part2() {
     part2::process(divan::black_box(include_str!("../input.txt",)), 75).unwrap();
 }
+
+/// Many starting stones, where spreading each blink over workers pays off.
+fn many_stones() -> Vec<stone::Stone> {
+    (0..2000).map(|i| i * 7919).collect()
+}
+
+#[divan::bench(args = [75, 1000])]
+fn count_sequential(blinks: u32) {
+    part2::count::<BigUint>(
+        &divan::black_box(many_stones()),
+        blinks,
+        &rules::RuleSet::default(),
+    )
+    .unwrap();
+}
+
+#[divan::bench(args = [75, 1000])]
+fn count_parallel(blinks: u32) {
+    parallel::count::<BigUint>(
+        &divan::black_box(many_stones()),
+        blinks,
+        &rules::RuleSet::default(),
+    )
+    .unwrap();
+}
@@ -9,4 +9,4 @@ fn main() -> miette::Result<()> {
     let result = process(file).context("process part 1")?;
     print!("{}", result);
     Ok(())
-}
\ No newline at end of file
+}
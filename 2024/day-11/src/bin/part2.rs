@@ -1,12 +1,71 @@
-use day_11::part2::process;
-use miette::Context;
+use day_11::{parallel, part2::process_with, rules::RuleSet, stats, stone, transition::Transition};
+use miette::{Context, IntoDiagnostic};
+
+const BLINKS: u32 = 75;
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
 
 #[tracing::instrument]
 fn main() -> miette::Result<()> {
     tracing_subscriber::fmt::init();
 
+    // `--rules <file>` swaps the AoC rules for a rule file
+    let rules = match arg_value("--rules") {
+        Some(path) => std::fs::read_to_string(&path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("read rules from {path}"))?
+            .parse::<RuleSet>()?,
+        None => RuleSet::default(),
+    };
+
     let file = include_str!("../../input.txt");
-    let result = process(file, 75).context("process part 2")?;
+
+    // `--blinks <n>` with `--modulo <prime>` or `--exact` powers the transition matrix instead
+    if let Some(blinks) = arg_value("--blinks") {
+        let blinks = blinks
+            .parse::<u64>()
+            .into_diagnostic()
+            .wrap_err("parse --blinks")?;
+        let transition = Transition::build(&stone::parse(file)?, &rules)?;
+        if let Some(prime) = arg_value("--modulo") {
+            let prime = prime
+                .parse::<u64>()
+                .into_diagnostic()
+                .wrap_err("parse --modulo")?;
+            print!("{}", transition.count_mod(blinks, prime)?);
+            return Ok(());
+        }
+        if std::env::args().any(|arg| arg == "--exact") {
+            print!("{}", transition.count_exact(blinks)?);
+            return Ok(());
+        }
+        let blinks = u32::try_from(blinks)
+            .into_diagnostic()
+            .wrap_err("iterate --blinks without --modulo or --exact")?;
+        let result = process_with(file, blinks, &rules).context("process part 2")?;
+        print!("{}", result);
+        return Ok(());
+    }
+
+    // `--stats <file>` also exports per-blink statistics as CSV
+    if let Some(path) = arg_value("--stats") {
+        let csv = std::fs::File::create(&path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("create {path}"))?;
+        let stats = stats::blink_stats(&stone::parse(file)?, &rules).take(BLINKS as usize + 1);
+        stats::write_csv(stats, std::io::BufWriter::new(csv)).context("export stats")?;
+    }
+
+    // `--parallel` spreads each blink over rayon workers
+    if std::env::args().any(|arg| arg == "--parallel") {
+        let result = parallel::count::<u128>(&stone::parse(file)?, BLINKS, &rules)?;
+        print!("{}", result);
+        return Ok(());
+    }
+
+    let result = process_with(file, BLINKS, &rules).context("process part 2")?;
     print!("{}", result);
     Ok(())
 }
@@ -1,2 +1,8 @@
+pub mod parallel;
 pub mod part1;
 pub mod part2;
+pub mod rules;
+pub mod sequence;
+pub mod stats;
+pub mod stone;
+pub mod transition;
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use num_traits::{CheckedAdd, One, Zero};
+use rayon::prelude::*;
+
+use crate::{
+    part2::{self, Counts},
+    rules::RuleSet,
+    stone::{BlinkError, Stone},
+};
+
+/// Number of shards the counts are split into. A value always lands in the same shard, so each
+/// shard of the next blink can be merged independently of the others.
+const SHARDS: usize = 64;
+
+fn shard_of(stone: Stone) -> usize {
+    let folded = stone as u64 ^ (stone >> 64) as u64;
+    (folded.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - SHARDS.trailing_zeros())) as usize
+}
+
+fn empty_shards<C>() -> Vec<Counts<C>> {
+    (0..SHARDS).map(|_| HashMap::new()).collect()
+}
+
+/// The initial counts, split into shards.
+pub fn initial_shards<C>(stones: &[Stone]) -> Result<Vec<Counts<C>>, BlinkError>
+where
+    C: One + CheckedAdd,
+{
+    let mut shards = empty_shards();
+    for &stone in stones {
+        part2::add(&mut shards[shard_of(stone)], stone, C::one())
+            .ok_or(BlinkError::CountOverflow { blink: 0 })?;
+    }
+    Ok(shards)
+}
+
+/// The sharded counts after blink number `blink`: every shard is expanded on its own worker into
+/// per-destination shards, which are then merged per destination in parallel.
+pub fn blink_shards<C>(
+    prev: Vec<Counts<C>>,
+    blink: u32,
+    rules: &RuleSet,
+) -> Result<Vec<Counts<C>>, BlinkError>
+where
+    C: Clone + CheckedAdd + Send + Sync,
+{
+    let expanded = prev
+        .into_par_iter()
+        .map(|shard| {
+            let mut out = empty_shards();
+            for (stone, count) in shard {
+                let next = rules
+                    .blink(stone)
+                    .ok_or(BlinkError::StoneOverflow { stone, blink })?;
+                for stone in next {
+                    part2::add(&mut out[shard_of(stone)], stone, count.clone())
+                        .ok_or(BlinkError::CountOverflow { blink })?;
+                }
+            }
+            Ok(out)
+        })
+        .collect::<Vec<Result<_, BlinkError>>>();
+
+    // report the error of the lowest shard, not whichever worker happened to fail first
+    let mut columns = (0..SHARDS)
+        .map(|_| Vec::with_capacity(expanded.len()))
+        .collect::<Vec<_>>();
+    for row in expanded {
+        for (column, counts) in columns.iter_mut().zip(row?) {
+            column.push(counts);
+        }
+    }
+
+    columns
+        .into_par_iter()
+        .map(|mut column| {
+            // merge into the largest partial map to re-insert as little as possible
+            let largest = (0..column.len()).max_by_key(|&i| column[i].len());
+            let mut merged = largest.map_or_else(HashMap::new, |i| column.swap_remove(i));
+            for counts in column {
+                for (stone, count) in counts {
+                    part2::add(&mut merged, stone, count)
+                        .ok_or(BlinkError::CountOverflow { blink })?;
+                }
+            }
+            Ok(merged)
+        })
+        .collect()
+}
+
+/// Same as [`part2::count`], with each blink's expansion and merging spread over rayon workers.
+pub fn count<C>(stones: &[Stone], blinks: u32, rules: &RuleSet) -> Result<C, BlinkError>
+where
+    C: Clone + Zero + One + CheckedAdd + Send + Sync,
+{
+    let shards = (1..=blinks).try_fold(initial_shards(stones)?, |prev, blink| {
+        blink_shards(prev, blink, rules)
+    })?;
+
+    shards.iter().try_fold(C::zero(), |acc, shard| {
+        acc.checked_add(&part2::total(shard, blinks)?)
+            .ok_or(BlinkError::CountOverflow { blink: blinks })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigUint;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::stone;
+
+    #[rstest]
+    #[case("125 17", 25)]
+    #[case("125 17", 75)]
+    #[case("0", 40)]
+    #[case("4022724 951333 0 21633 5857 97 702 6", 75)]
+    fn test_matches_sequential(#[case] input: &str, #[case] blinks: u32) -> miette::Result<()> {
+        let stones = stone::parse(input)?;
+        let rules = RuleSet::default();
+        assert_eq!(
+            part2::count::<u128>(&stones, blinks, &rules)?,
+            count::<u128>(&stones, blinks, &rules)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_many_stones() -> miette::Result<()> {
+        let stones = (0..2000).map(|i| i * 7919).collect::<Vec<Stone>>();
+        let rules = RuleSet::default();
+        assert_eq!(
+            part2::count::<u128>(&stones, 40, &rules)?,
+            count::<u128>(&stones, 40, &rules)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_big_counts() -> miette::Result<()> {
+        let stones = stone::parse("125 17")?;
+        let rules = RuleSet::default();
+        assert!(matches!(
+            count::<u128>(&stones, 1000, &rules),
+            Err(BlinkError::CountOverflow { .. })
+        ));
+        assert_eq!(
+            part2::count::<BigUint>(&stones, 1000, &rules)?,
+            count::<BigUint>(&stones, 1000, &rules)?
+        );
+        Ok(())
+    }
+}
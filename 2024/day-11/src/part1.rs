@@ -1,38 +1,33 @@
-use miette::miette;
-use nom::{
-    character::complete::{self, space1},
-    multi::separated_list1,
-    IResult,
+use itertools::Itertools;
+
+use crate::{
+    rules::RuleSet,
+    stone::{self, BlinkError, Stone},
 };
 
-fn parse(input: &str) -> IResult<&str, Vec<u64>> {
-    separated_list1(space1, complete::u64)(input)
+/// The whole line of stones after `blinks` blinks, in order.
+pub fn stones_after(
+    stones: Vec<Stone>,
+    blinks: u32,
+    rules: &RuleSet,
+) -> Result<Vec<Stone>, BlinkError> {
+    (1..=blinks).try_fold(stones, |stones, blink| {
+        stones
+            .iter()
+            .map(|&stone| {
+                rules
+                    .blink(stone)
+                    .ok_or(BlinkError::StoneOverflow { stone, blink })
+            })
+            .flatten_ok()
+            .collect()
+    })
 }
 
 #[tracing::instrument]
 pub fn process(input: &str) -> miette::Result<usize> {
-    let (_, stones) = parse(input).map_err(|e| miette!("parse failed {}", e))?;
-
-    let count = (0..25)
-        .fold(stones, |stones, _| {
-            stones
-                .iter()
-                .flat_map(|&stone| match stone {
-                    0 => [Some(1), None],
-                    n => {
-                        let s = n.to_string();
-                        if s.len() % 2 == 0 {
-                            let (a, b) = s.split_at(s.len() / 2);
-                            [a.parse().ok(), b.parse().ok()]
-                        } else {
-                            [Some(n * 2024), None]
-                        }
-                    }
-                })
-                .flatten()
-                .collect()
-        })
-        .len();
+    let stones = stone::parse(input)?;
+    let count = stones_after(stones, 25, &RuleSet::default())?.len();
 
     Ok(count)
 }
@@ -48,4 +43,27 @@ mod tests {
         assert_eq!(55312, process(input)?);
         Ok(())
     }
+
+    #[test]
+    fn test_stones_after() -> miette::Result<()> {
+        let stones = stone::parse("125 17")?;
+        assert_eq!(
+            vec![
+                2097446912, 14168, 4048, 2, 0, 2, 4, 40, 48, 2024, 40, 48, 80, 96, 2, 8, 6, 7, 6,
+                0, 3, 2
+            ],
+            stones_after(stones, 6, &RuleSet::default())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stone_overflow() -> miette::Result<()> {
+        let stones = stone::parse("100000000000000000000000000000000000000")?;
+        assert!(matches!(
+            stones_after(stones, 1, &RuleSet::default()),
+            Err(BlinkError::StoneOverflow { blink: 1, .. })
+        ));
+        Ok(())
+    }
 }
@@ -1,54 +1,100 @@
 use std::collections::HashMap;
 
-use itertools::Itertools;
-use miette::miette;
-use nom::{
-    character::complete::{self, space1},
-    multi::separated_list1,
-    IResult,
+use num_traits::{CheckedAdd, One, Zero};
+
+use crate::{
+    rules::RuleSet,
+    stone::{self, BlinkError, Stone},
 };
-use num_traits::Euclid;
 
-fn parse(input: &str) -> IResult<&str, Vec<u64>> {
-    separated_list1(space1, complete::u64)(input)
+pub(crate) fn add<C: CheckedAdd>(
+    counts: &mut HashMap<Stone, C>,
+    stone: Stone,
+    count: C,
+) -> Option<()> {
+    match counts.get_mut(&stone) {
+        Some(total) => *total = total.checked_add(&count)?,
+        None => {
+            counts.insert(stone, count);
+        }
+    }
+    Some(())
 }
 
-#[tracing::instrument]
-pub fn process(input: &str, blinks: u32) -> miette::Result<usize> {
-    let (_, stones) = parse(input).map_err(|e| miette!("parse failed {}", e))?;
-    let stones = stones.iter().copied().counts();
-
-    let count = (0..blinks)
-        .fold(stones, |prev, _| {
-            let mut new = HashMap::new();
-            prev.iter().for_each(|(&num, &count)| match num {
-                0 => {
-                    new.entry(1).and_modify(|v| *v += count).or_insert(count);
-                }
-                n => {
-                    let digit_count = n.checked_ilog10().unwrap_or(0) + 1;
-                    if digit_count % 2 == 0 {
-                        let (a, b) = n.div_rem_euclid(&10u64.pow(digit_count / 2));
-                        for n in [a, b] {
-                            new.entry(n).and_modify(|v| *v += count).or_insert(count);
-                        }
-                    } else {
-                        new.entry(n * 2024)
-                            .and_modify(|v| *v += count)
-                            .or_insert(count);
-                    }
-                }
-            });
-            new
-        })
+/// How many stones carry each value.
+pub type Counts<C> = HashMap<Stone, C>;
+
+pub fn initial_counts<C>(stones: &[Stone]) -> Result<Counts<C>, BlinkError>
+where
+    C: One + CheckedAdd,
+{
+    let mut counts = HashMap::new();
+    for &stone in stones {
+        add(&mut counts, stone, C::one()).ok_or(BlinkError::CountOverflow { blink: 0 })?;
+    }
+    Ok(counts)
+}
+
+/// The counts after blink number `blink`, given the counts before it.
+pub fn blink_counts<C>(
+    prev: Counts<C>,
+    blink: u32,
+    rules: &RuleSet,
+) -> Result<Counts<C>, BlinkError>
+where
+    C: Clone + CheckedAdd,
+{
+    let mut new = HashMap::new();
+    for (stone, count) in prev {
+        let next = rules
+            .blink(stone)
+            .ok_or(BlinkError::StoneOverflow { stone, blink })?;
+        for stone in next {
+            add(&mut new, stone, count.clone()).ok_or(BlinkError::CountOverflow { blink })?;
+        }
+    }
+    Ok(new)
+}
+
+pub fn total<C>(counts: &Counts<C>, blink: u32) -> Result<C, BlinkError>
+where
+    C: Zero + CheckedAdd,
+{
+    counts
         .values()
-        .sum();
+        .try_fold(C::zero(), |acc, count| acc.checked_add(count))
+        .ok_or(BlinkError::CountOverflow { blink })
+}
+
+/// Number of stones after `blinks` blinks, counted in `C` (e.g. `u128` or `BigUint`).
+pub fn count<C>(stones: &[Stone], blinks: u32, rules: &RuleSet) -> Result<C, BlinkError>
+where
+    C: Clone + Zero + One + CheckedAdd,
+{
+    let counts = (1..=blinks).try_fold(initial_counts(stones)?, |prev, blink| {
+        blink_counts(prev, blink, rules)
+    })?;
+
+    total(&counts, blinks)
+}
+
+#[tracing::instrument]
+pub fn process(input: &str, blinks: u32) -> miette::Result<u128> {
+    process_with(input, blinks, &RuleSet::default())
+}
+
+#[tracing::instrument]
+pub fn process_with(input: &str, blinks: u32, rules: &RuleSet) -> miette::Result<u128> {
+    let stones = stone::parse(input)?;
+    let count = count(&stones, blinks, rules)?;
 
     Ok(count)
 }
 
 #[cfg(test)]
 mod tests {
+    use num_bigint::BigUint;
+
     use super::*;
 
     #[test]
@@ -58,4 +104,30 @@ mod tests {
         assert_eq!(55312, process(input, 25)?);
         Ok(())
     }
+
+    #[test]
+    fn test_process_with_variant() -> miette::Result<()> {
+        // every stone splits into itself and a 0, and 0 stays 0: doubling each blink
+        let rules = "* -> x, 0".parse()?;
+        assert_eq!(3 << 10, process_with("125 17 0", 10, &rules)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_overflow() -> miette::Result<()> {
+        let stones = stone::parse("125 17")?;
+        let rules = RuleSet::default();
+        assert!(matches!(
+            count::<u64>(&stones, 150, &rules),
+            Err(BlinkError::CountOverflow { .. })
+        ));
+
+        let narrow = count::<u128>(&stones, 150, &rules)?;
+        let wide = count::<BigUint>(&stones, 150, &rules)?;
+        assert_eq!(BigUint::from(narrow), wide);
+
+        assert!(count::<u128>(&stones, 300, &rules).is_err());
+        assert!(count::<BigUint>(&stones, 300, &rules)? > BigUint::from(u128::MAX));
+        Ok(())
+    }
 }
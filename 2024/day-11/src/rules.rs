@@ -0,0 +1,357 @@
+use std::str::FromStr;
+
+use miette::{Diagnostic, SourceSpan};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{self, space0, space1},
+    combinator::{all_consuming, cut, map, opt, value, verify},
+    error::ErrorKind,
+    multi::separated_list1,
+    sequence::{delimited, preceded, separated_pair, tuple},
+    IResult,
+};
+use num_traits::Euclid;
+use thiserror::Error;
+
+use crate::stone::{digit_count, Stone};
+
+/// The AoC rules, in the rule-file format.
+pub const AOC_RULES: &str = "\
+value == 0 -> 1
+digits % 2 == 0 -> split
+* -> x * 2024
+";
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("invalid rule on line {line}")]
+#[diagnostic(help(
+    "rules look like `value == 0 -> 1`, `digits % 2 == 0 -> split` or `* -> x * 2024 + 1`"
+))]
+pub struct RuleParseError {
+    line: usize,
+    #[source_code]
+    src: String,
+    #[label("{expected}")]
+    span: SourceSpan,
+    expected: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn test<T: Ord>(self, lhs: T, rhs: T) -> bool {
+        match self {
+            Cmp::Eq => lhs == rhs,
+            Cmp::Ne => lhs != rhs,
+            Cmp::Lt => lhs < rhs,
+            Cmp::Le => lhs <= rhs,
+            Cmp::Gt => lhs > rhs,
+            Cmp::Ge => lhs >= rhs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Value(Cmp, Stone),
+    Digits(Cmp, u32),
+    /// `digits % modulus <cmp> remainder`
+    DigitsMod {
+        modulus: u32,
+        cmp: Cmp,
+        remainder: u32,
+    },
+}
+
+impl Condition {
+    fn test(self, stone: Stone) -> bool {
+        match self {
+            Condition::Value(cmp, value) => cmp.test(stone, value),
+            Condition::Digits(cmp, digits) => cmp.test(digit_count(stone), digits),
+            Condition::DigitsMod {
+                modulus,
+                cmp,
+                remainder,
+            } => cmp.test(digit_count(stone) % modulus, remainder),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Constant(Stone),
+    /// The left and right halves of the digits. With an odd digit count the left half is longer.
+    SplitDigits,
+    /// `x * mul + add`
+    Affine {
+        mul: Stone,
+        add: Stone,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    /// All must hold for the rule to apply. Empty matches every stone.
+    pub conditions: Vec<Condition>,
+    pub outputs: Vec<Output>,
+}
+
+/// Ordered rules: a stone is replaced according to the first rule whose conditions all hold,
+/// and stays unchanged if none does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            rules: vec![
+                Rule {
+                    conditions: vec![Condition::Value(Cmp::Eq, 0)],
+                    outputs: vec![Output::Constant(1)],
+                },
+                Rule {
+                    conditions: vec![Condition::DigitsMod {
+                        modulus: 2,
+                        cmp: Cmp::Eq,
+                        remainder: 0,
+                    }],
+                    outputs: vec![Output::SplitDigits],
+                },
+                Rule {
+                    conditions: vec![],
+                    outputs: vec![Output::Affine { mul: 2024, add: 0 }],
+                },
+            ],
+        }
+    }
+}
+
+impl RuleSet {
+    /// The stones that replace `stone` after one blink, or `None` if one outgrows [`Stone`].
+    pub fn blink(&self, stone: Stone) -> Option<Vec<Stone>> {
+        let Some(rule) = self.rules.iter().find(|rule| {
+            rule.conditions
+                .iter()
+                .all(|condition| condition.test(stone))
+        }) else {
+            return Some(vec![stone]);
+        };
+
+        let mut stones = Vec::with_capacity(rule.outputs.len() + 1);
+        for output in &rule.outputs {
+            match *output {
+                Output::Constant(value) => stones.push(value),
+                Output::SplitDigits => {
+                    let (a, b) = stone.div_rem_euclid(&10u128.pow(digit_count(stone) / 2));
+                    stones.extend([a, b]);
+                }
+                Output::Affine { mul, add } => {
+                    stones.push(stone.checked_mul(mul)?.checked_add(add)?);
+                }
+            }
+        }
+        Some(stones)
+    }
+}
+
+fn cmp(input: &str) -> IResult<&str, Cmp> {
+    alt((
+        value(Cmp::Eq, tag("==")),
+        value(Cmp::Ne, tag("!=")),
+        value(Cmp::Le, tag("<=")),
+        value(Cmp::Ge, tag(">=")),
+        value(Cmp::Lt, tag("<")),
+        value(Cmp::Gt, tag(">")),
+    ))(input)
+}
+
+fn padded<'a, O>(
+    parser: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+    delimited(space0, parser, space0)
+}
+
+fn condition(input: &str) -> IResult<&str, Condition> {
+    alt((
+        map(
+            tuple((
+                preceded(tag("digits"), padded(tag("%"))),
+                // past the `%` it can only be this condition, so a zero modulus is not retried
+                cut(verify(complete::u32, |&modulus| modulus != 0)),
+                padded(cmp),
+                complete::u32,
+            )),
+            |(_, modulus, cmp, remainder)| Condition::DigitsMod {
+                modulus,
+                cmp,
+                remainder,
+            },
+        ),
+        map(
+            preceded(tag("digits"), tuple((padded(cmp), complete::u32))),
+            |(cmp, digits)| Condition::Digits(cmp, digits),
+        ),
+        map(
+            preceded(tag("value"), tuple((padded(cmp), complete::u128))),
+            |(cmp, value)| Condition::Value(cmp, value),
+        ),
+    ))(input)
+}
+
+fn conditions(input: &str) -> IResult<&str, Vec<Condition>> {
+    alt((
+        value(vec![], tag("*")),
+        separated_list1(delimited(space1, tag("and"), space1), condition),
+    ))(input)
+}
+
+fn output(input: &str) -> IResult<&str, Output> {
+    alt((
+        value(Output::SplitDigits, tag("split")),
+        map(
+            preceded(
+                tag("x"),
+                tuple((
+                    opt(preceded(padded(tag("*")), complete::u128)),
+                    opt(preceded(padded(tag("+")), complete::u128)),
+                )),
+            ),
+            |(mul, add)| Output::Affine {
+                mul: mul.unwrap_or(1),
+                add: add.unwrap_or(0),
+            },
+        ),
+        map(complete::u128, Output::Constant),
+    ))(input)
+}
+
+fn rule(input: &str) -> IResult<&str, Rule> {
+    map(
+        all_consuming(separated_pair(
+            padded(conditions),
+            tag("->"),
+            padded(separated_list1(padded(tag(",")), output)),
+        )),
+        |(conditions, outputs)| Rule {
+            conditions,
+            outputs,
+        },
+    )(input)
+}
+
+/// One rule per line; blank lines and `#` comments are ignored.
+impl FromStr for RuleSet {
+    type Err = RuleParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rules = s
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i, line.split('#').next().unwrap_or_default()))
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let offset = |part: &str| part.as_ptr() as usize - s.as_ptr() as usize;
+                rule(line).map(|(_, rule)| rule).map_err(|error| {
+                    let (span, expected) = match error {
+                        nom::Err::Failure(error) if error.code == ErrorKind::Verify => {
+                            let digits = error
+                                .input
+                                .find(|c: char| !c.is_ascii_digit())
+                                .unwrap_or(error.input.len());
+                            (
+                                (offset(error.input), digits),
+                                "expected a modulus of at least 1",
+                            )
+                        }
+                        _ => (
+                            (offset(line), line.len()),
+                            "expected `<conditions> -> <outputs>`",
+                        ),
+                    };
+                    RuleParseError {
+                        line: i + 1,
+                        src: s.to_string(),
+                        span: span.into(),
+                        expected,
+                    }
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { rules })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_aoc_rules() {
+        assert_eq!(RuleSet::default(), AOC_RULES.parse().unwrap());
+    }
+
+    #[rstest]
+    #[case(0, vec![1])]
+    #[case(1, vec![2024])]
+    #[case(10, vec![1, 0])]
+    #[case(99, vec![9, 9])]
+    #[case(999, vec![2021976])]
+    #[case(1000, vec![10, 0])]
+    fn test_default_blink(#[case] stone: Stone, #[case] expected: Vec<Stone>) {
+        assert_eq!(Some(expected), RuleSet::default().blink(stone));
+    }
+
+    #[test]
+    fn test_variant() {
+        let rules: RuleSet = "
+            # comments and blank lines are skipped
+
+            value < 10 and value != 7  -> x * 3 + 1, 0
+            digits >= 3 and digits % 2 == 1 -> split
+            value >= 10 -> x + 5
+        "
+        .parse()
+        .unwrap();
+
+        assert_eq!(Some(vec![16, 0]), rules.blink(5));
+        assert_eq!(Some(vec![7]), rules.blink(7));
+        assert_eq!(Some(vec![12, 3]), rules.blink(123));
+        assert_eq!(Some(vec![17]), rules.blink(12));
+
+        let doubling: RuleSet = "* -> x * 2".parse().unwrap();
+        assert_eq!(None, doubling.blink(Stone::MAX));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = "value == 0 -> 1\n* -> y * 2\n"
+            .parse::<RuleSet>()
+            .unwrap_err();
+        assert_eq!(2, err.line);
+        assert_eq!(16, err.span.offset());
+    }
+
+    #[test]
+    fn test_zero_modulus() {
+        let err = "value == 0 -> 1\ndigits >= 2 and digits % 00 == 0 -> split\n"
+            .parse::<RuleSet>()
+            .unwrap_err();
+        assert_eq!(2, err.line);
+        assert_eq!((41, 2), (err.span.offset(), err.span.len()));
+        assert_eq!("expected a modulus of at least 1", err.expected);
+    }
+}
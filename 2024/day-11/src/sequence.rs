@@ -0,0 +1,173 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{
+    rules::RuleSet,
+    stone::{BlinkError, Stone},
+};
+
+/// Positional queries on the line of stones after some blinks, without materialising it.
+///
+/// Every stone evolves independently and in place, so the line is a forest: each stone's
+/// subtree after `n` blinks is a contiguous run whose length only depends on `(stone, n)`.
+#[derive(Debug, Clone)]
+pub struct StoneLine {
+    stones: Vec<Stone>,
+    rules: RuleSet,
+    /// Number of stones that `stone` turns into after the given number of blinks.
+    sizes: HashMap<(Stone, u32), u128>,
+}
+
+impl StoneLine {
+    pub fn new(stones: Vec<Stone>) -> Self {
+        Self::with_rules(stones, RuleSet::default())
+    }
+
+    pub fn with_rules(stones: Vec<Stone>, rules: RuleSet) -> Self {
+        Self {
+            stones,
+            rules,
+            sizes: HashMap::new(),
+        }
+    }
+
+    fn children(
+        &self,
+        stone: Stone,
+        remaining: u32,
+        blinks: u32,
+    ) -> Result<Vec<Stone>, BlinkError> {
+        self.rules.blink(stone).ok_or(BlinkError::StoneOverflow {
+            stone,
+            blink: blinks - remaining + 1,
+        })
+    }
+
+    fn size(&mut self, stone: Stone, remaining: u32, blinks: u32) -> Result<u128, BlinkError> {
+        if remaining == 0 {
+            return Ok(1);
+        }
+        if let Some(&size) = self.sizes.get(&(stone, remaining)) {
+            return Ok(size);
+        }
+
+        let mut size = 0u128;
+        for child in self.children(stone, remaining, blinks)? {
+            size = size
+                .checked_add(self.size(child, remaining - 1, blinks)?)
+                .ok_or(BlinkError::CountOverflow { blink: blinks })?;
+        }
+        self.sizes.insert((stone, remaining), size);
+        Ok(size)
+    }
+
+    /// Number of stones after `blinks` blinks.
+    pub fn len(&mut self, blinks: u32) -> Result<u128, BlinkError> {
+        self.stones
+            .clone()
+            .into_iter()
+            .try_fold(0u128, |len, stone| {
+                len.checked_add(self.size(stone, blinks, blinks)?)
+                    .ok_or(BlinkError::CountOverflow { blink: blinks })
+            })
+    }
+
+    /// The `index`-th stone (0-based) after `blinks` blinks, or `None` past the end of the line.
+    pub fn nth(&mut self, index: u128, blinks: u32) -> Result<Option<Stone>, BlinkError> {
+        Ok(self.slice(index..index.saturating_add(1), blinks)?.pop())
+    }
+
+    /// The stones at `range` after `blinks` blinks, clamped to the end of the line.
+    pub fn slice(&mut self, range: Range<u128>, blinks: u32) -> Result<Vec<Stone>, BlinkError> {
+        let mut slice = Vec::new();
+        let mut offset = 0;
+        for i in 0..self.stones.len() {
+            if offset >= range.end {
+                break;
+            }
+            offset = self.collect(self.stones[i], blinks, blinks, offset, &range, &mut slice)?;
+        }
+        Ok(slice)
+    }
+
+    /// Appends the part of `stone`'s subtree that falls in `range`, given that the subtree starts
+    /// at `offset`. Returns the offset right after the subtree.
+    fn collect(
+        &mut self,
+        stone: Stone,
+        remaining: u32,
+        blinks: u32,
+        offset: u128,
+        range: &Range<u128>,
+        slice: &mut Vec<Stone>,
+    ) -> Result<u128, BlinkError> {
+        let size = self.size(stone, remaining, blinks)?;
+        let end = offset.saturating_add(size);
+        if end <= range.start || range.end <= offset {
+            return Ok(end);
+        }
+        if remaining == 0 {
+            slice.push(stone);
+            return Ok(end);
+        }
+
+        let mut offset = offset;
+        for child in self.children(stone, remaining, blinks)? {
+            if offset >= range.end {
+                break;
+            }
+            offset = self.collect(child, remaining - 1, blinks, offset, range, slice)?;
+        }
+        Ok(end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{part1, part2, stone};
+
+    #[rstest]
+    #[case("125 17")]
+    #[case("0 1 10 99 999")]
+    fn test_against_brute_force(#[case] input: &str) -> miette::Result<()> {
+        let stones = stone::parse(input)?;
+        let mut line = StoneLine::new(stones.clone());
+
+        for blinks in 0..=15 {
+            let expected = part1::stones_after(stones.clone(), blinks, &RuleSet::default())?;
+            let len = expected.len() as u128;
+
+            assert_eq!(len, line.len(blinks)?);
+            assert_eq!(expected, line.slice(0..len, blinks)?);
+            for (index, &stone) in expected.iter().enumerate() {
+                assert_eq!(Some(stone), line.nth(index as u128, blinks)?);
+            }
+            assert_eq!(None, line.nth(len, blinks)?);
+
+            let (start, end) = (len / 3, len * 2 / 3 + 1);
+            assert_eq!(
+                expected[start as usize..end as usize],
+                line.slice(start..end, blinks)?
+            );
+            assert_eq!(
+                expected[start as usize..],
+                line.slice(start..len + 10, blinks)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_many_blinks() -> miette::Result<()> {
+        let stones = stone::parse("125 17")?;
+        let mut line = StoneLine::new(stones.clone());
+
+        let len = line.len(75)?;
+        assert_eq!(part2::count::<u128>(&stones, 75, &RuleSet::default())?, len);
+        assert!(line.nth(len - 1, 75)?.is_some());
+        assert_eq!(5, line.slice(len / 2..len / 2 + 5, 75)?.len());
+        Ok(())
+    }
+}
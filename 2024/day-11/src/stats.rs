@@ -0,0 +1,168 @@
+use std::{collections::HashSet, io::Write};
+
+use miette::IntoDiagnostic;
+
+use crate::{
+    part2::{self, Counts},
+    rules::RuleSet,
+    stone::{BlinkError, Stone},
+};
+
+/// What the line of stones looks like after one blink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlinkStats {
+    pub blink: u32,
+    pub total: u128,
+    pub distinct: usize,
+    pub largest: Stone,
+    /// Values that appear for the first time at this blink, in increasing order.
+    pub new_values: Vec<Stone>,
+}
+
+/// Yields the statistics of the initial line (blink 0) and then of every blink, forever or
+/// until a count or stone overflows.
+pub struct BlinkStatsIter<'a> {
+    rules: &'a RuleSet,
+    counts: Option<Result<Counts<u128>, BlinkError>>,
+    seen: HashSet<Stone>,
+    blink: u32,
+}
+
+pub fn blink_stats<'a>(stones: &[Stone], rules: &'a RuleSet) -> BlinkStatsIter<'a> {
+    BlinkStatsIter {
+        rules,
+        counts: Some(part2::initial_counts(stones)),
+        seen: HashSet::new(),
+        blink: 0,
+    }
+}
+
+impl Iterator for BlinkStatsIter<'_> {
+    type Item = Result<BlinkStats, BlinkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let counts = match self.counts.take()? {
+            Ok(counts) => counts,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let stats = part2::total(&counts, self.blink).map(|total| {
+            let mut new_values = counts
+                .keys()
+                .copied()
+                .filter(|&stone| self.seen.insert(stone))
+                .collect::<Vec<_>>();
+            new_values.sort_unstable();
+
+            BlinkStats {
+                blink: self.blink,
+                total,
+                distinct: counts.len(),
+                largest: counts.keys().copied().max().unwrap_or_default(),
+                new_values,
+            }
+        });
+
+        if stats.is_ok() {
+            self.blink += 1;
+            self.counts = Some(part2::blink_counts(counts, self.blink, self.rules));
+        }
+        Some(stats)
+    }
+}
+
+/// One row per blink; the new values are only counted.
+pub fn write_csv(
+    stats: impl IntoIterator<Item = Result<BlinkStats, BlinkError>>,
+    mut writer: impl Write,
+) -> miette::Result<()> {
+    writeln!(writer, "blink,total,distinct,largest,new_values").into_diagnostic()?;
+    for stats in stats {
+        let stats = stats?;
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            stats.blink,
+            stats.total,
+            stats.distinct,
+            stats.largest,
+            stats.new_values.len()
+        )
+        .into_diagnostic()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{part1, stone};
+
+    #[test]
+    fn test_against_brute_force() -> miette::Result<()> {
+        let stones = stone::parse("125 17")?;
+        let rules = RuleSet::default();
+
+        let mut seen = HashSet::new();
+        for (blink, stats) in blink_stats(&stones, &rules).take(16).enumerate() {
+            let stats = stats?;
+            let line = part1::stones_after(stones.clone(), blink as u32, &rules)?;
+            let distinct = line.iter().copied().collect::<HashSet<_>>();
+            let mut new_values = distinct.difference(&seen).copied().collect::<Vec<_>>();
+            new_values.sort_unstable();
+            seen.extend(distinct.iter().copied());
+
+            assert_eq!(
+                BlinkStats {
+                    blink: blink as u32,
+                    total: line.len() as u128,
+                    distinct: distinct.len(),
+                    largest: line.iter().copied().max().unwrap(),
+                    new_values,
+                },
+                stats
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_saturation() -> miette::Result<()> {
+        // everything reachable from 0 shows up within the first few dozen blinks
+        let rules = RuleSet::default();
+        let new_values = blink_stats(&[0], &rules)
+            .take(100)
+            .map(|stats| stats.map(|stats| stats.new_values.len()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(54, new_values.iter().sum::<usize>());
+        assert!(new_values[50..].iter().all(|&n| n == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_overflow_ends_stream() {
+        let rules = "* -> x * 10".parse().unwrap();
+        let stats = blink_stats(&[1], &rules).collect::<Vec<_>>();
+
+        assert_eq!(40, stats.len());
+        assert!(matches!(
+            stats.last(),
+            Some(Err(BlinkError::StoneOverflow { blink: 39, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_write_csv() -> miette::Result<()> {
+        let stones = stone::parse("125 17")?;
+        let rules = RuleSet::default();
+        let mut csv = Vec::new();
+        write_csv(blink_stats(&stones, &rules).take(3), &mut csv)?;
+
+        assert_eq!(
+            "blink,total,distinct,largest,new_values\n0,2,2,125,2\n1,3,3,253000,3\n2,4,4,14168,4\n",
+            String::from_utf8(csv).unwrap()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use miette::{miette, Diagnostic};
+use nom::{
+    character::complete::{self, space1},
+    multi::separated_list1,
+    IResult,
+};
+use thiserror::Error;
+
+/// Stones are engraved with `u64` values, but multiplying by 2024 can outgrow them.
+pub type Stone = u128;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum BlinkError {
+    #[error("stone {stone} overflows on blink {blink}")]
+    StoneOverflow { stone: Stone, blink: u32 },
+
+    #[error("stone count overflows on blink {blink}")]
+    #[diagnostic(help("count with a wider type, e.g. `BigUint`"))]
+    CountOverflow { blink: u32 },
+}
+
+fn parse_stones(input: &str) -> IResult<&str, Vec<Stone>> {
+    separated_list1(space1, complete::u128)(input)
+}
+
+pub fn parse(input: &str) -> miette::Result<Vec<Stone>> {
+    let (_, stones) = parse_stones(input).map_err(|e| miette!("parse failed {}", e))?;
+    Ok(stones)
+}
+
+pub fn digit_count(stone: Stone) -> u32 {
+    stone.checked_ilog10().unwrap_or(0) + 1
+}
@@ -0,0 +1,695 @@
+use std::collections::{HashMap, VecDeque};
+
+use miette::Diagnostic;
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+use thiserror::Error;
+
+use crate::{
+    rules::RuleSet,
+    stone::{BlinkError, Stone},
+};
+
+/// Most distinct values the closed set may hold.
+const MAX_VALUES: usize = 1 << 16;
+/// Most recurrent values that are powered as a dense matrix.
+const MAX_DENSE: usize = 256;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum TransitionError {
+    #[error("stone {stone} overflows while building the reachable values")]
+    StoneOverflow { stone: Stone },
+
+    #[error("more than {limit} distinct values are reachable")]
+    #[diagnostic(help("the rules must keep stone values bounded for a finite transition matrix"))]
+    TooManyValues { limit: usize },
+
+    #[error("{len} values recur, more than the {limit} that can be powered as a dense matrix")]
+    #[diagnostic(help("count modulo a prime instead, or iterate with `part2::count::<BigUint>`"))]
+    TooManyRecurrent { len: usize, limit: usize },
+
+    #[error("modulus {modulus} is not prime")]
+    NotPrime { modulus: u64 },
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Blink(#[from] BlinkError),
+}
+
+/// Arithmetic the stone counts are computed in.
+trait Arithmetic {
+    type Value: Clone;
+
+    fn zero(&self) -> Self::Value;
+    fn is_zero(&self, a: &Self::Value) -> bool;
+    fn number(&self, n: u64) -> Self::Value;
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+    fn mul(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+struct Modulo(u64);
+
+impl Arithmetic for Modulo {
+    type Value = u64;
+
+    fn zero(&self) -> u64 {
+        0
+    }
+
+    fn is_zero(&self, a: &u64) -> bool {
+        *a == 0
+    }
+
+    fn number(&self, n: u64) -> u64 {
+        n % self.0
+    }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 + *b as u128) % self.0 as u128) as u64
+    }
+
+    fn mul(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 * *b as u128) % self.0 as u128) as u64
+    }
+}
+
+struct Exact;
+
+impl Arithmetic for Exact {
+    type Value = BigUint;
+
+    fn zero(&self) -> BigUint {
+        BigUint::zero()
+    }
+
+    fn is_zero(&self, a: &BigUint) -> bool {
+        a.is_zero()
+    }
+
+    fn number(&self, n: u64) -> BigUint {
+        BigUint::from(n)
+    }
+
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a + b
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a * b
+    }
+}
+
+/// The closed set of stone values reachable from the initial stones, with the sparse matrix of
+/// how many stones of each value one stone of another value turns into.
+#[derive(Debug, Clone)]
+pub struct Transition {
+    values: Vec<Stone>,
+    /// Per value index: `(next value index, multiplicity)`.
+    edges: Vec<Vec<(usize, u64)>>,
+    initial: Vec<(usize, u64)>,
+}
+
+impl Transition {
+    pub fn build(stones: &[Stone], rules: &RuleSet) -> Result<Self, TransitionError> {
+        let mut index = HashMap::new();
+        let mut values = Vec::new();
+        let mut intern = |stone: Stone, values: &mut Vec<Stone>| {
+            *index.entry(stone).or_insert_with(|| {
+                values.push(stone);
+                values.len() - 1
+            })
+        };
+
+        let mut initial = HashMap::new();
+        for &stone in stones {
+            *initial.entry(intern(stone, &mut values)).or_insert(0) += 1;
+        }
+
+        let mut edges = Vec::new();
+        while edges.len() < values.len() {
+            if values.len() > MAX_VALUES {
+                return Err(TransitionError::TooManyValues { limit: MAX_VALUES });
+            }
+
+            let stone = values[edges.len()];
+            let next = rules
+                .blink(stone)
+                .ok_or(TransitionError::StoneOverflow { stone })?;
+
+            let mut row: Vec<(usize, u64)> = Vec::new();
+            for stone in next {
+                let to = intern(stone, &mut values);
+                match row.iter_mut().find(|(i, _)| *i == to) {
+                    Some((_, multiplicity)) => *multiplicity += 1,
+                    None => row.push((to, 1)),
+                }
+            }
+            edges.push(row);
+        }
+
+        let mut initial = initial.into_iter().collect::<Vec<_>>();
+        initial.sort_unstable();
+
+        Ok(Self {
+            values,
+            edges,
+            initial,
+        })
+    }
+
+    /// Number of distinct reachable values.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Values that can still carry stones after arbitrarily many blinks: everything downstream
+    /// of a cycle. Found by repeatedly peeling off values that nothing leads to; also returns
+    /// how many blinks it takes for every other value to run dry.
+    fn recurrent(&self) -> (Vec<bool>, u64) {
+        let mut in_degree = vec![0usize; self.len()];
+        for row in &self.edges {
+            for &(to, _) in row {
+                in_degree[to] += 1;
+            }
+        }
+
+        let mut recurrent = vec![true; self.len()];
+        let mut depth = vec![0u64; self.len()];
+        let mut drained = 0;
+        let mut queue = (0..self.len())
+            .filter(|&i| in_degree[i] == 0)
+            .collect::<VecDeque<_>>();
+        while let Some(i) = queue.pop_front() {
+            recurrent[i] = false;
+            drained = drained.max(depth[i] + 1);
+            for &(to, _) in &self.edges[i] {
+                depth[to] = depth[to].max(depth[i] + 1);
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    queue.push_back(to);
+                }
+            }
+        }
+        (recurrent, drained)
+    }
+
+    /// One blink of the sparse matrix applied to the per-value counts.
+    fn step<A: Arithmetic>(&self, counts: &[A::Value], arithmetic: &A) -> Vec<A::Value> {
+        let mut next = vec![arithmetic.zero(); self.len()];
+        for (count, row) in counts.iter().zip(&self.edges) {
+            for &(to, multiplicity) in row {
+                let added = arithmetic.mul(count, &arithmetic.number(multiplicity));
+                next[to] = arithmetic.add(&next[to], &added);
+            }
+        }
+        next
+    }
+
+    fn initial_counts<A: Arithmetic>(&self, arithmetic: &A) -> Vec<A::Value> {
+        let mut counts = vec![arithmetic.zero(); self.len()];
+        for &(i, count) in &self.initial {
+            counts[i] = arithmetic.number(count);
+        }
+        counts
+    }
+
+    fn total<A: Arithmetic>(counts: &[A::Value], arithmetic: &A) -> A::Value {
+        counts.iter().fold(arithmetic.zero(), |total, count| {
+            arithmetic.add(&total, count)
+        })
+    }
+
+    /// Powers the dense matrix of the recurrent values, after draining the rest.
+    fn count_dense<A: Arithmetic>(
+        &self,
+        blinks: u64,
+        arithmetic: &A,
+    ) -> Result<A::Value, TransitionError> {
+        let (recurrent, drained) = self.recurrent();
+        let core = (0..self.len())
+            .filter(|&i| recurrent[i])
+            .collect::<Vec<_>>();
+        if core.len() > MAX_DENSE {
+            return Err(TransitionError::TooManyRecurrent {
+                len: core.len(),
+                limit: MAX_DENSE,
+            });
+        }
+
+        // the non-recurrent values form a DAG that runs dry after `drained` blinks
+        let mut counts = self.initial_counts(arithmetic);
+        let warm_up = blinks.min(drained);
+        for _ in 0..warm_up {
+            counts = self.step(&counts, arithmetic);
+        }
+        let mut blinks = blinks - warm_up;
+        if blinks == 0 {
+            return Ok(Self::total(&counts, arithmetic));
+        }
+
+        // the recurrent values are closed under blinking
+        let position = core
+            .iter()
+            .enumerate()
+            .map(|(position, &i)| (i, position))
+            .collect::<HashMap<_, _>>();
+        let mut matrix = vec![vec![arithmetic.zero(); core.len()]; core.len()];
+        for (row, &from) in core.iter().enumerate() {
+            for &(to, multiplicity) in &self.edges[from] {
+                matrix[row][position[&to]] = arithmetic.number(multiplicity);
+            }
+        }
+        let mut vector = core.iter().map(|&i| counts[i].clone()).collect::<Vec<_>>();
+
+        // exponentiation by squaring, applying each power of two straight to the vector
+        loop {
+            if blinks & 1 == 1 {
+                vector = multiply_vector(&vector, &matrix, arithmetic);
+            }
+            blinks >>= 1;
+            if blinks == 0 {
+                break;
+            }
+            matrix = multiply_matrix(&matrix, &matrix, arithmetic);
+        }
+
+        Ok(Self::total(&vector, arithmetic))
+    }
+
+    /// Finds the shortest linear recurrence of the totals with Berlekamp-Massey, then raises `x`
+    /// to `blinks` by squaring modulo its characteristic polynomial.
+    fn count_recurrence(&self, blinks: u64, field: &Modulo) -> u64 {
+        // the matrix's characteristic polynomial bounds the recurrence length by `len`
+        let mut counts = self.initial_counts(field);
+        let mut totals = Vec::with_capacity(2 * self.len() + 2);
+        for _ in 0..2 * self.len() + 2 {
+            totals.push(Self::total(&counts, field));
+            if totals.len() as u64 > blinks {
+                return totals[blinks as usize];
+            }
+            counts = self.step(&counts, field);
+        }
+
+        let recurrence = berlekamp_massey(&totals, field);
+        if recurrence.is_empty() {
+            return 0;
+        }
+        let power = field.pow_mod_poly(blinks, &recurrence);
+        power
+            .iter()
+            .zip(&totals)
+            .fold(0, |total, (coefficient, term)| {
+                field.add(&total, &field.mul(coefficient, term))
+            })
+    }
+
+    /// Number of stones after `blinks` blinks, modulo `prime`.
+    pub fn count_mod(&self, blinks: u64, prime: u64) -> Result<u64, TransitionError> {
+        if !is_prime(prime) {
+            return Err(TransitionError::NotPrime { modulus: prime });
+        }
+
+        let field = Modulo(prime);
+        match self.count_dense(blinks, &field) {
+            Err(TransitionError::TooManyRecurrent { .. }) => {
+                Ok(self.count_recurrence(blinks, &field))
+            }
+            result => result,
+        }
+    }
+
+    /// Number of stones after `blinks` blinks. The result has about `0.6 * blinks` bits, so this
+    /// is only practical for up to around a million blinks; use [`Self::count_mod`] beyond that.
+    pub fn count_exact(&self, blinks: u64) -> Result<BigUint, TransitionError> {
+        match self.count_dense(blinks, &Exact) {
+            Err(TransitionError::TooManyRecurrent { .. }) => Ok(self.count_crt(blinks)),
+            result => result,
+        }
+    }
+
+    /// More bits than the count after `blinks` blinks can have: no value turns into more
+    /// stones than the largest sum of multiplicities in a row.
+    fn count_bits(&self, blinks: u64) -> u64 {
+        let bits = |n: u64| (u64::BITS - n.leading_zeros()) as u64;
+        let initial = self.initial.iter().map(|&(_, count)| count).sum::<u64>();
+        let growth = self
+            .edges
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&(_, multiplicity)| multiplicity)
+                    .sum::<u64>()
+            })
+            .max()
+            .unwrap_or(0);
+        // `bits(growth - 1)` is the ceiling of `log2(growth)`
+        bits(initial).saturating_add(blinks.saturating_mul(bits(growth.saturating_sub(1))))
+    }
+
+    /// The exact count from [`Self::count_recurrence`] modulo enough primes above `2^62` for
+    /// their product to exceed it, combined by the Chinese remainder theorem.
+    fn count_crt(&self, blinks: u64) -> BigUint {
+        let primes = (1u64 << 62..=u64::MAX >> 1)
+            .rev()
+            .step_by(2)
+            .filter(|&n| is_prime(n))
+            .take(self.count_bits(blinks).div_ceil(62).max(1) as usize);
+
+        let mut count = BigUint::zero();
+        let mut modulus = BigUint::one();
+        for prime in primes {
+            let field = Modulo(prime);
+            let residue = |n: &BigUint| (n % prime).to_u64().expect("residue below a u64 prime");
+            // add the multiple of `modulus` that makes `count` right modulo `prime` too
+            let remainder = self.count_recurrence(blinks, &field);
+            let offset = field.mul(
+                &field.sub(&remainder, &residue(&count)),
+                &field.inverse(residue(&modulus)),
+            );
+            count += &modulus * offset;
+            modulus *= prime;
+        }
+        count
+    }
+}
+
+impl Modulo {
+    fn pow(&self, mut base: u64, mut exponent: u64) -> u64 {
+        let mut result = 1 % self.0;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = self.mul(&result, &base);
+            }
+            base = self.mul(&base, &base);
+            exponent >>= 1;
+        }
+        result
+    }
+
+    fn sub(&self, a: &u64, b: &u64) -> u64 {
+        self.add(a, &(self.0 - b % self.0))
+    }
+
+    /// Inverse by Fermat's little theorem, so only valid for a prime modulus.
+    fn inverse(&self, a: u64) -> u64 {
+        self.pow(a, self.0 - 2)
+    }
+
+    /// `x^exponent` reduced modulo the characteristic polynomial of `recurrence`, as the
+    /// coefficients of `x^0..x^len`.
+    fn pow_mod_poly(&self, mut exponent: u64, recurrence: &[u64]) -> Vec<u64> {
+        let len = recurrence.len();
+        let multiply = |a: &[u64], b: &[u64]| {
+            let mut product = vec![0; 2 * len];
+            for (i, x) in a.iter().enumerate().filter(|(_, x)| **x != 0) {
+                for (j, y) in b.iter().enumerate() {
+                    product[i + j] = self.add(&product[i + j], &self.mul(x, y));
+                }
+            }
+            // x^len = recurrence[0] x^(len-1) + ... + recurrence[len-1]
+            for i in (len..2 * len).rev() {
+                let high = product[i];
+                if high == 0 {
+                    continue;
+                }
+                for (k, c) in recurrence.iter().enumerate() {
+                    let at = i - 1 - k;
+                    product[at] = self.add(&product[at], &self.mul(&high, c));
+                }
+            }
+            product.truncate(len);
+            product
+        };
+
+        let mut result = vec![0; len];
+        result[0] = 1 % self.0;
+        let mut base = vec![0; len];
+        if len == 1 {
+            base[0] = recurrence[0];
+        } else {
+            base[1] = 1;
+        }
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = multiply(&result, &base);
+            }
+            base = multiply(&base, &base);
+            exponent >>= 1;
+        }
+        result
+    }
+}
+
+/// Shortest `c` with `s[n] = c[0] s[n-1] + ... + c[len-1] s[n-len]` for every term.
+fn berlekamp_massey(terms: &[u64], field: &Modulo) -> Vec<u64> {
+    // connection polynomials `1 - c[0] x - c[1] x^2 - ...` of the current and last-longer fits
+    let mut current = vec![0; terms.len() + 1];
+    let mut previous = vec![0; terms.len() + 1];
+    current[0] = 1;
+    previous[0] = 1;
+    let mut len = 0;
+    let mut shift = 0;
+    let mut previous_discrepancy = 1;
+    for (n, term) in terms.iter().enumerate() {
+        shift += 1;
+        let discrepancy = (1..=len).fold(*term, |d, j| {
+            field.add(&d, &field.mul(&current[j], &terms[n - j]))
+        });
+        if discrepancy == 0 {
+            continue;
+        }
+
+        let last = current.clone();
+        let scale = field.mul(&discrepancy, &field.inverse(previous_discrepancy));
+        for j in shift..current.len() {
+            current[j] = field.sub(&current[j], &field.mul(&scale, &previous[j - shift]));
+        }
+        if 2 * len > n {
+            continue;
+        }
+        len = n + 1 - len;
+        previous = last;
+        previous_discrepancy = discrepancy;
+        shift = 0;
+    }
+
+    current[1..=len].iter().map(|c| field.sub(&0, c)).collect()
+}
+
+/// Deterministic Miller-Rabin, exact for every `u64`.
+fn is_prime(n: u64) -> bool {
+    const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+    if n < 2 {
+        return false;
+    }
+    if let Some(&p) = WITNESSES.iter().find(|&&p| n.is_multiple_of(p)) {
+        return n == p;
+    }
+
+    let field = Modulo(n);
+    let odd = (n - 1) >> (n - 1).trailing_zeros();
+    WITNESSES.iter().all(|&witness| {
+        let mut x = field.pow(witness, odd);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        let mut exponent = odd;
+        while exponent * 2 < n - 1 {
+            x = field.mul(&x, &x);
+            exponent *= 2;
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+fn multiply_vector<A: Arithmetic>(
+    vector: &[A::Value],
+    matrix: &[Vec<A::Value>],
+    arithmetic: &A,
+) -> Vec<A::Value> {
+    let mut result = vec![arithmetic.zero(); vector.len()];
+    for (count, row) in vector.iter().zip(matrix) {
+        if arithmetic.is_zero(count) {
+            continue;
+        }
+        for (total, entry) in result.iter_mut().zip(row) {
+            *total = arithmetic.add(total, &arithmetic.mul(count, entry));
+        }
+    }
+    result
+}
+
+fn multiply_matrix<A: Arithmetic>(
+    a: &[Vec<A::Value>],
+    b: &[Vec<A::Value>],
+    arithmetic: &A,
+) -> Vec<Vec<A::Value>> {
+    a.iter()
+        .map(|row| multiply_vector(row, b, arithmetic))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{part2, stone};
+
+    const PRIME: u64 = 1_000_000_007;
+
+    /// Expected totals for blinks `0..=blinks` from the iterative counter.
+    fn counter_totals(stones: &[Stone], blinks: u32, rules: &RuleSet) -> Vec<BigUint> {
+        let mut counts = part2::initial_counts::<BigUint>(stones).unwrap();
+        let mut totals = vec![part2::total(&counts, 0).unwrap()];
+        for blink in 1..=blinks {
+            counts = part2::blink_counts(counts, blink, rules).unwrap();
+            totals.push(part2::total(&counts, blink).unwrap());
+        }
+        totals
+    }
+
+    #[rstest]
+    #[case("125 17")]
+    #[case("0")]
+    #[case("0 1 2024")]
+    fn test_against_counter(#[case] input: &str) -> miette::Result<()> {
+        let stones = stone::parse(input)?;
+        let rules = RuleSet::default();
+        let transition = Transition::build(&stones, &rules)?;
+
+        for (blinks, expected) in counter_totals(&stones, 150, &rules).into_iter().enumerate() {
+            let blinks = blinks as u64;
+            // the exact powers are big-integer matrix products, so only spot-check those
+            if [0, 1, 2, 25, 75, 150].contains(&blinks) {
+                assert_eq!(expected, transition.count_exact(blinks)?);
+            }
+            assert_eq!(
+                expected % PRIME,
+                BigUint::from(transition.count_mod(blinks, PRIME)?)
+            );
+        }
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("125 17")]
+    #[case("0 1 2024")]
+    fn test_recurrence_matches_dense(#[case] input: &str) -> miette::Result<()> {
+        let stones = stone::parse(input)?;
+        let rules = RuleSet::default();
+        let transition = Transition::build(&stones, &rules)?;
+        let expected = counter_totals(&stones, 300, &rules);
+
+        // both below and above the number of terms the recurrence is fitted on
+        let field = Modulo(PRIME);
+        for blinks in [0, 1, 25, 75, 150, 300] {
+            assert_eq!(
+                &expected[blinks] % PRIME,
+                BigUint::from(transition.count_recurrence(blinks as u64, &field))
+            );
+        }
+        for blinks in [1_000, 1_000_000, u64::MAX] {
+            assert_eq!(
+                transition.count_dense(blinks, &field)?,
+                transition.count_recurrence(blinks, &field)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_core() -> miette::Result<()> {
+        // thousands of recurrent values: too many for the dense matrix but fine modulo a prime
+        let stones = stone::parse("4022724 951333 0 21633 5857 97 702 6")?;
+        let rules = RuleSet::default();
+        let transition = Transition::build(&stones, &rules)?;
+
+        assert!(matches!(
+            transition.count_dense(75, &Exact),
+            Err(TransitionError::TooManyRecurrent { .. })
+        ));
+        let expected = &counter_totals(&stones, 75, &rules)[75];
+        assert_eq!(*expected, transition.count_exact(75)?);
+        assert_eq!(
+            expected % PRIME,
+            BigUint::from(transition.count_mod(75, PRIME)?)
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("125 17")]
+    #[case("0 1 2024")]
+    fn test_crt_matches_counter(#[case] input: &str) -> miette::Result<()> {
+        let stones = stone::parse(input)?;
+        let rules = RuleSet::default();
+        let transition = Transition::build(&stones, &rules)?;
+        let expected = counter_totals(&stones, 300, &rules);
+
+        // past the terms the recurrence is fitted on, and past a single prime's worth of bits
+        for blinks in [0, 1, 75, 150, 300] {
+            assert_eq!(expected[blinks], transition.count_crt(blinks as u64));
+        }
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(0, false)]
+    #[case(1, false)]
+    #[case(2, true)]
+    #[case(561, false)]
+    #[case(PRIME, true)]
+    #[case(3_215_031_751, false)]
+    #[case(18_446_744_073_709_551_557, true)]
+    fn test_is_prime(#[case] n: u64, #[case] expected: bool) {
+        assert_eq!(expected, is_prime(n));
+    }
+
+    #[test]
+    fn test_composite_modulus() -> miette::Result<()> {
+        let transition = Transition::build(&[125, 17], &RuleSet::default())?;
+        assert!(matches!(
+            transition.count_mod(75, 1 << 32),
+            Err(TransitionError::NotPrime { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_astronomical_blinks() -> miette::Result<()> {
+        // every stone becomes two zeros, so there are 2^n stones after n blinks
+        let rules = "* -> 0, 0".parse()?;
+        let transition = Transition::build(&[17], &rules)?;
+
+        let blinks = 1_000_000_000_000u64;
+        let expected = BigUint::from(2u8).modpow(&BigUint::from(blinks), &BigUint::from(PRIME));
+        assert_eq!(
+            expected,
+            BigUint::from(transition.count_mod(blinks, PRIME)?)
+        );
+
+        let stones = stone::parse("125 17")?;
+        let transition = Transition::build(&stones, &RuleSet::default())?;
+        assert!(transition.count_mod(blinks, PRIME)? < PRIME);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unbounded_rules() -> miette::Result<()> {
+        let rules = "* -> x + 1".parse()?;
+        assert!(matches!(
+            Transition::build(&[0], &rules),
+            Err(TransitionError::TooManyValues { .. })
+        ));
+        Ok(())
+    }
+}
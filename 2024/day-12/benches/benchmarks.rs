@@ -1,4 +1,5 @@
 use day_12::*;
+use scanline::{Area, Perimeter, Sides};
 
 fn main() {
     // Run registered benchmarks.
@@ -7,16 +8,36 @@ fn main() {
 
 #[divan::bench]
 fn part1() {
-    part1::process(divan::black_box(include_str!(
-        "../input.txt",
-    )))
-    .unwrap();
+    part1::process(divan::black_box(include_str!("../input.txt",))).unwrap();
 }
 
 #[divan::bench]
 fn part2() {
-    part2::process(divan::black_box(include_str!(
-        "../input.txt",
-    )))
-    .unwrap();
-}
\ No newline at end of file
+    part2::process(divan::black_box(include_str!("../input.txt",))).unwrap();
+}
+
+const GENERATED_SIZE: usize = 4000;
+
+/// A 4000x4000 map of blobs over six plants.
+fn generated_map() -> String {
+    let mut rng = testing::Rng::new(41);
+    testing::generate::blobs(&mut rng, GENERATED_SIZE, GENERATED_SIZE, 6)
+}
+
+#[divan::bench(sample_count = 10)]
+fn generated_sequential(bencher: divan::Bencher) {
+    let input = generated_map();
+    bencher.bench(|| scanline::scan::<(Area, Perimeter, Sides)>(divan::black_box(&input)));
+}
+
+#[divan::bench(args = [1, 2, 4, 8], sample_count = 10)]
+fn generated_bands(bencher: divan::Bencher, threads: usize) {
+    let input = generated_map();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    bencher.bench(|| {
+        pool.install(|| band::scan_par::<(Area, Perimeter, Sides)>(divan::black_box(&input)))
+    });
+}
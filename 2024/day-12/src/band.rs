@@ -0,0 +1,147 @@
+use rayon::prelude::*;
+
+use crate::scanline::{chunks, Accumulator, Chunk, Options, Regions, RowChunk};
+
+/// Fewest rows worth handing to a worker of their own.
+const MIN_BAND_ROWS: usize = 64;
+
+/// A horizontal band of the map scanned on its own: regions that reach its first or last row
+/// are only partial until the band is stitched to its neighbours.
+struct Band<A> {
+    regions: Regions<A>,
+    top: Vec<RowChunk>,
+    bottom: Vec<RowChunk>,
+}
+
+fn scan_band<A: Accumulator>(
+    lines: &[&str],
+    y: usize,
+    options: &Options,
+    new: &impl Fn(&Chunk) -> A,
+) -> Band<A> {
+    let mut regions = Regions::new();
+    let mut top = None;
+    let mut prev_row = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let row = chunks(y + i, line, &options.plants)
+            .map(|chunk| regions.push_chunk(chunk, new))
+            .collect::<Vec<_>>();
+        regions.join(&prev_row, &row, options.neighbourhood);
+        top.get_or_insert_with(|| row.clone());
+        prev_row = row;
+    }
+
+    Band {
+        regions,
+        top: top.unwrap_or_default(),
+        bottom: prev_row,
+    }
+}
+
+/// Every region of the map, in reading order of their first cells, like [`scan`] but with the
+/// map split into bands that are scanned in parallel.
+///
+/// [`scan`]: crate::scanline::scan
+pub fn scan_par<A: Accumulator + Send>(input: &str) -> Vec<A> {
+    scan_par_with(input, &Options::default(), A::new)
+}
+
+/// [`scan_par`] with `options`, starting each region with `new` instead of [`Accumulator::new`].
+pub fn scan_par_with<A, F>(input: &str, options: &Options, new: F) -> Vec<A>
+where
+    A: Accumulator + Send,
+    F: Fn(&Chunk) -> A + Sync,
+{
+    let lines = input.lines().collect::<Vec<_>>();
+    let band_rows = lines
+        .len()
+        .div_ceil(rayon::current_num_threads())
+        .max(MIN_BAND_ROWS);
+    scan_bands(&lines, band_rows, options, new)
+}
+
+/// Scans bands of `band_rows` rows in parallel, then stitches them top to bottom. Each band's
+/// nodes follow those of the bands above it, so the union-find still keeps the earliest origin.
+fn scan_bands<A, F>(lines: &[&str], band_rows: usize, options: &Options, new: F) -> Vec<A>
+where
+    A: Accumulator + Send,
+    F: Fn(&Chunk) -> A + Sync,
+{
+    let bands = lines
+        .par_chunks(band_rows)
+        .enumerate()
+        .map(|(i, lines)| scan_band(lines, i * band_rows, options, &new))
+        .collect::<Vec<_>>();
+
+    let mut regions = Regions::new();
+    let mut prev_bottom: Vec<RowChunk> = Vec::new();
+    for band in bands {
+        let offset = regions.append(band.regions);
+        let shift = |mut row: Vec<RowChunk>| {
+            for chunk in &mut row {
+                chunk.node += offset;
+            }
+            row
+        };
+        let top = shift(band.top);
+        regions.join(&prev_bottom, &top, options.neighbourhood);
+        prev_bottom = shift(band.bottom);
+    }
+
+    regions.into_regions().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{
+        region::{self, Cells, Region},
+        scanline::{self, Area, Neighbourhood, Perimeter, Sides},
+        testing::{generate, Rng},
+    };
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    #[case(3)]
+    #[case(7)]
+    fn test_bands_match_sequential(#[case] band_rows: usize) {
+        let mut rng = Rng::new(41);
+        for _ in 0..200 {
+            let (width, height) = (1 + rng.below(16), 1 + rng.below(16));
+            let input = match rng.below(3) {
+                0 => generate::noise(&mut rng, width, height, 3),
+                1 => generate::enclaves(&mut rng, width, height),
+                _ => generate::rings_with_holes(&mut rng, width, height),
+            };
+            let options = Options {
+                neighbourhood: [Neighbourhood::Four, Neighbourhood::Eight][rng.below(2)],
+                ..Default::default()
+            };
+            let lines = input.lines().collect::<Vec<_>>();
+            let banded = scan_bands(&lines, band_rows, &options, |chunk| {
+                Region::with_cells(chunk, Cells::Include)
+            })
+            .into_iter()
+            .map(Region::sort_cells)
+            .collect::<Vec<_>>();
+            assert_eq!(
+                region::regions_with(&input, Cells::Include, &options),
+                banded,
+                "map:\n{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_scan_par() {
+        let mut rng = Rng::new(42);
+        let input = generate::noise(&mut rng, 300, 300, 3);
+        assert_eq!(
+            scanline::scan::<(Area, Perimeter, Sides)>(&input),
+            scan_par::<(Area, Perimeter, Sides)>(&input)
+        );
+    }
+}
@@ -1,11 +1,62 @@
-use day_12::part2::process;
-use miette::Context;
+use day_12::{
+    part2::process,
+    pricing::{self, Aoc, Breakdown},
+    region,
+    scanline::{Neighbourhood, Options},
+    sides,
+};
+use miette::{Context, IntoDiagnostic};
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
 
 #[tracing::instrument]
 fn main() -> miette::Result<()> {
     tracing_subscriber::fmt::init();
 
     let file = include_str!("../../input.txt");
+
+    // `--eight` joins diagonal neighbours and `--plants <case-insensitive|Aa,Bb>` plant classes
+    // for the inventory and sides below
+    let options = Options {
+        neighbourhood: match std::env::args().any(|arg| arg == "--eight") {
+            true => Neighbourhood::Eight,
+            false => Neighbourhood::Four,
+        },
+        plants: arg_value("--plants")
+            .map(|plants| plants.parse())
+            .transpose()?
+            .unwrap_or_default(),
+    };
+
+    // `--regions <table|json>` prints the region inventory instead, `--cells` with member cells
+    if let Some(format) = arg_value("--regions") {
+        let cells = match std::env::args().any(|arg| arg == "--cells") {
+            true => region::Cells::Include,
+            false => region::Cells::Omit,
+        };
+        let stdout = std::io::stdout().lock();
+        region::write_inventory(file, format.parse()?, cells, &options, stdout)
+            .into_diagnostic()?;
+        return Ok(());
+    }
+
+    // `--sides <svg|json>` exports where the sides of every region are instead
+    if let Some(format) = arg_value("--sides") {
+        let stdout = std::io::stdout().lock();
+        sides::write_sides(file, format.parse()?, &options, stdout).into_diagnostic()?;
+        return Ok(());
+    }
+
+    // `--breakdown` prints the price of each region and the total instead
+    if std::env::args().any(|arg| arg == "--breakdown") {
+        let regions = region::regions(file, region::Cells::Omit);
+        let prices = pricing::breakdown(&regions, &Aoc::PART2)?;
+        print!("{}", Breakdown(&prices));
+        return Ok(());
+    }
+
     let result = process(file).context("process part 2")?;
     print!("{}", result);
     Ok(())
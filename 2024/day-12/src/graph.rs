@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    region::{self, Cells, Region},
+    scanline::Options,
+};
+
+/// Fence of a region split by what lies on its other side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Fences {
+    /// Fence shared with regions this one encloses.
+    pub inner: u64,
+    /// All other fence, including the fence along the edge of the map.
+    pub outer: u64,
+}
+
+/// Which regions share fence with which, and how they nest.
+#[derive(Debug, Clone)]
+pub struct RegionGraph {
+    /// The regions, in reading order of their first cells; indices refer to this list.
+    pub regions: Vec<Region>,
+    /// Length of fence shared by two regions, keyed with the smaller index first.
+    pub edges: BTreeMap<(usize, usize), u64>,
+    /// Length of fence each region has along the edge of the map.
+    pub border: Vec<u64>,
+    neighbours: Vec<Vec<(usize, u64)>>,
+    encloser: Vec<Option<usize>>,
+    depth: Vec<usize>,
+}
+
+impl RegionGraph {
+    pub fn build(input: &str, options: &Options) -> Self {
+        let regions = region::regions_with(input, Cells::Include, options);
+
+        let mut labels = input
+            .lines()
+            .map(|line| vec![usize::MAX; line.chars().count()])
+            .collect::<Vec<_>>();
+        for (i, region) in regions.iter().enumerate() {
+            for &(x, y) in region.cells.iter().flatten() {
+                labels[y][x] = i;
+            }
+        }
+
+        let mut edges = BTreeMap::new();
+        let mut border = vec![0; regions.len()];
+        let mut fence = |a: usize, b: Option<usize>| match b {
+            Some(b) if a != b => *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1,
+            Some(_) => {}
+            None => border[a] += 1,
+        };
+        for (y, row) in labels.iter().enumerate() {
+            for (x, &label) in row.iter().enumerate() {
+                if x == 0 {
+                    fence(label, None);
+                }
+                fence(label, row.get(x + 1).copied());
+                if y == 0 || labels[y - 1].get(x).is_none() {
+                    fence(label, None);
+                }
+                fence(label, labels.get(y + 1).and_then(|row| row.get(x)).copied());
+            }
+        }
+
+        let mut neighbours = vec![Vec::new(); regions.len()];
+        for (&(a, b), &length) in &edges {
+            neighbours[a].push((b, length));
+            neighbours[b].push((a, length));
+        }
+        let (encloser, depth) = enclosers(&neighbours, &border);
+
+        Self {
+            regions,
+            edges,
+            border,
+            neighbours,
+            encloser,
+            depth,
+        }
+    }
+
+    /// Regions sharing fence with `region`, with the length of fence shared.
+    pub fn neighbours(&self, region: usize) -> &[(usize, u64)] {
+        &self.neighbours[region]
+    }
+
+    /// The innermost region that encloses `region`: every way from `region` to the edge of the
+    /// map crosses it. `None` for regions that are not enclosed by any single region.
+    pub fn encloser(&self, region: usize) -> Option<usize> {
+        self.encloser[region]
+    }
+
+    /// How many regions enclose `region`, one inside the other.
+    pub fn depth(&self, region: usize) -> usize {
+        self.depth[region]
+    }
+
+    /// Whether `outer` encloses `inner`, directly or through regions in between.
+    pub fn encloses(&self, outer: usize, inner: usize) -> bool {
+        let mut region = inner;
+        while self.depth[region] > self.depth[outer] {
+            region = self.encloser[region].unwrap();
+        }
+        region == outer && inner != outer
+    }
+
+    pub fn fences(&self, region: usize) -> Fences {
+        let inner = self.neighbours[region]
+            .iter()
+            .filter(|&&(other, _)| self.encloses(region, other))
+            .map(|&(_, length)| length)
+            .sum::<u64>();
+        Fences {
+            inner,
+            outer: self.regions[region].perimeter - inner,
+        }
+    }
+}
+
+/// Immediate dominators of the regions in the graph rooted at the outside of the map, with the
+/// iterative algorithm of Cooper, Harvey and Kennedy, plus the depth of each in that tree.
+fn enclosers(neighbours: &[Vec<(usize, u64)>], border: &[u64]) -> (Vec<Option<usize>>, Vec<usize>) {
+    let outside = neighbours.len();
+    let adjacent = |node: usize| -> Vec<usize> {
+        match node == outside {
+            true => (0..outside).filter(|&i| border[i] > 0).collect(),
+            false => neighbours[node]
+                .iter()
+                .map(|&(other, _)| other)
+                .chain((border[node] > 0).then_some(outside))
+                .collect(),
+        }
+    };
+
+    // reverse postorder of a depth-first search from the outside
+    let mut order = Vec::with_capacity(outside + 1);
+    let mut visited = vec![false; outside + 1];
+    let mut stack = vec![(outside, adjacent(outside), 0)];
+    visited[outside] = true;
+    while let Some((node, next, i)) = stack.last_mut() {
+        match next.get(*i).copied() {
+            Some(child) => {
+                *i += 1;
+                if !visited[child] {
+                    visited[child] = true;
+                    let children = adjacent(child);
+                    stack.push((child, children, 0));
+                }
+            }
+            None => {
+                order.push(*node);
+                stack.pop();
+            }
+        }
+    }
+    order.reverse();
+    let mut position = vec![usize::MAX; outside + 1];
+    for (i, &node) in order.iter().enumerate() {
+        position[node] = i;
+    }
+
+    let mut dominator = vec![usize::MAX; outside + 1];
+    dominator[outside] = outside;
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &node in &order[1..] {
+            let mut processed = adjacent(node)
+                .into_iter()
+                .filter(|&other| dominator[other] != usize::MAX);
+            let Some(first) = processed.next() else {
+                continue;
+            };
+            let new = processed.fold(first, |mut a, mut b| {
+                while a != b {
+                    while position[a] > position[b] {
+                        a = dominator[a];
+                    }
+                    while position[b] > position[a] {
+                        b = dominator[b];
+                    }
+                }
+                a
+            });
+            if dominator[node] != new {
+                dominator[node] = new;
+                changed = true;
+            }
+        }
+    }
+
+    let encloser = (0..outside)
+        .map(|node| Some(dominator[node]).filter(|&d| d != outside && d != usize::MAX))
+        .collect::<Vec<_>>();
+    let mut depth = vec![0; outside];
+    for &node in &order[1..] {
+        if let Some(parent) = encloser[node] {
+            depth[node] = depth[parent] + 1;
+        }
+    }
+    (encloser, depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::testing::{generate, Rng};
+
+    #[test]
+    fn test_enclaves() {
+        let graph = RegionGraph::build("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO\n", &Options::default());
+        assert_eq!(5, graph.regions.len());
+        assert_eq!(vec![20, 0, 0, 0, 0], graph.border);
+        for x in 1..5 {
+            assert_eq!(Some(0), graph.encloser(x));
+            assert_eq!(1, graph.depth(x));
+            assert_eq!(vec![(0, 4)], graph.neighbours(x));
+        }
+        assert_eq!((None, 0), (graph.encloser(0), graph.depth(0)));
+        assert_eq!(
+            Fences {
+                inner: 16,
+                outer: 20
+            },
+            graph.fences(0)
+        );
+        assert_eq!(Fences { inner: 0, outer: 4 }, graph.fences(1));
+    }
+
+    #[test]
+    fn test_nesting() {
+        let input = "AAAAAAA\nABBBBBA\nABCCCBA\nABCDCBA\nABCCCBA\nABBBBBA\nAAAAAAA\n";
+        let graph = RegionGraph::build(input, &Options::default());
+        let plants = graph.regions.iter().map(|r| r.plant).collect::<String>();
+        assert_eq!("ABCD", plants);
+        assert_eq!(
+            vec![None, Some(0), Some(1), Some(2)],
+            (0..4).map(|i| graph.encloser(i)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![0, 1, 2, 3],
+            (0..4).map(|i| graph.depth(i)).collect::<Vec<_>>()
+        );
+        assert!(graph.encloses(0, 3));
+        assert!(!graph.encloses(3, 0));
+        assert_eq!(
+            Fences {
+                inner: 20,
+                outer: 28
+            },
+            graph.fences(0)
+        );
+        assert_eq!(
+            Fences {
+                inner: 12,
+                outer: 20
+            },
+            graph.fences(1)
+        );
+    }
+
+    #[rstest]
+    // two enclaves side by side are each enclosed by the ring, not by each other
+    #[case("AAAA\nABCA\nAAAA\n", vec![None, Some(0), Some(0)])]
+    // a region on the edge of the map is never enclosed
+    #[case("AAB\nACA\nAAA\n", vec![None, None, Some(0)])]
+    // a ring with a gap to the edge does not enclose
+    #[case("AAA\nABA\nA.A\n", vec![None, None, None])]
+    fn test_enclosers(#[case] input: &str, #[case] expected: Vec<Option<usize>>) {
+        let graph = RegionGraph::build(input, &Options::default());
+        let enclosers = (0..graph.regions.len())
+            .map(|i| graph.encloser(i))
+            .collect::<Vec<_>>();
+        assert_eq!(expected, enclosers);
+    }
+
+    #[test]
+    fn test_fence_accounting() {
+        let mut rng = Rng::new(43);
+        for _ in 0..200 {
+            let (width, height) = (1 + rng.below(12), 1 + rng.below(12));
+            let input = match rng.below(2) {
+                0 => generate::enclaves(&mut rng, width, height),
+                _ => generate::rings_with_holes(&mut rng, width, height),
+            };
+            let graph = RegionGraph::build(&input, &Options::default());
+            for (i, region) in graph.regions.iter().enumerate() {
+                let shared = graph.neighbours(i).iter().map(|&(_, l)| l).sum::<u64>();
+                assert_eq!(region.perimeter, shared + graph.border[i], "map:\n{input}");
+                let fences = graph.fences(i);
+                assert_eq!(region.perimeter, fences.inner + fences.outer);
+                if let Some(encloser) = graph.encloser(i) {
+                    assert_eq!(0, graph.border[i]);
+                    assert_eq!(graph.depth(encloser) + 1, graph.depth(i));
+                }
+            }
+        }
+    }
+}
@@ -1,2 +1,13 @@
+pub mod band;
+pub mod graph;
 pub mod part1;
 pub mod part2;
+pub mod pricing;
+pub mod region;
+pub mod scanline;
+pub mod sides;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+#[cfg(test)]
+mod oracle;
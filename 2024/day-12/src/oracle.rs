@@ -0,0 +1,249 @@
+//! Straightforward reference implementation the scanline engine is tested against: flood fill
+//! for the regions, neighbour counting for perimeters and corner counting for sides.
+
+use std::collections::HashSet;
+
+use crate::{
+    region::{BoundingBox, Cell, Region},
+    scanline::{Neighbourhood, Options},
+};
+
+const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const DIAGONALS: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+pub struct Map {
+    rows: Vec<Vec<char>>,
+}
+
+impl Map {
+    pub fn parse(input: &str) -> Self {
+        Self {
+            rows: input.lines().map(|line| line.chars().collect()).collect(),
+        }
+    }
+
+    fn get(&self, (x, y): (isize, isize)) -> Option<char> {
+        let row = self.rows.get(usize::try_from(y).ok()?)?;
+        row.get(usize::try_from(x).ok()?).copied()
+    }
+
+    /// Every region with its cells, in reading order of their first cells.
+    pub fn regions(&self, options: &Options) -> Vec<Region> {
+        let mut seen = HashSet::new();
+        let mut regions = Vec::new();
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, &plant) in row.iter().enumerate() {
+                if seen.insert((x, y)) {
+                    regions.push(self.flood_fill(plant, (x, y), options, &mut seen));
+                }
+            }
+        }
+        regions
+    }
+
+    fn flood_fill(
+        &self,
+        plant: char,
+        origin: Cell,
+        options: &Options,
+        seen: &mut HashSet<Cell>,
+    ) -> Region {
+        let plant = options.plants.class(plant);
+        let neighbours = match options.neighbourhood {
+            Neighbourhood::Four => &DIRECTIONS[..],
+            Neighbourhood::Eight => &[DIRECTIONS, DIAGONALS].concat(),
+        };
+
+        let mut cells = vec![origin];
+        let mut stack = vec![origin];
+        while let Some((x, y)) = stack.pop() {
+            for (dx, dy) in neighbours {
+                let next = (x as isize + dx, y as isize + dy);
+                if self.get(next).map(|next| options.plants.class(next)) == Some(plant) {
+                    let next = (next.0 as usize, next.1 as usize);
+                    if seen.insert(next) {
+                        cells.push(next);
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+        cells.sort_unstable_by_key(|&(x, y)| (y, x));
+
+        let members = cells
+            .iter()
+            .map(|&(x, y)| (x as isize, y as isize))
+            .collect::<HashSet<_>>();
+        let inside = |cell: (isize, isize)| members.contains(&cell);
+
+        let mut perimeter = 0;
+        let mut corners = 0;
+        for &(x, y) in &members {
+            perimeter += DIRECTIONS
+                .iter()
+                .filter(|(dx, dy)| !inside((x + dx, y + dy)))
+                .count() as u64;
+
+            // a polygon has as many sides as corners: convex where both neighbours towards a
+            // diagonal are outside, concave where both are inside but the diagonal is not
+            for (dx, dy) in DIAGONALS {
+                let horizontal = inside((x + dx, y));
+                let vertical = inside((x, y + dy));
+                let diagonal = inside((x + dx, y + dy));
+                if (!horizontal && !vertical) || (horizontal && vertical && !diagonal) {
+                    corners += 1;
+                }
+            }
+        }
+
+        Region {
+            plant,
+            origin,
+            area: cells.len() as u64,
+            perimeter,
+            sides: corners,
+            bounds: BoundingBox {
+                x: cells.iter().map(|c| c.0).min().unwrap()
+                    ..cells.iter().map(|c| c.0).max().unwrap() + 1,
+                y: origin.1..cells.last().unwrap().1 + 1,
+            },
+            cells: Some(cells),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{
+        region::{self, Cells},
+        scanline::Plants,
+        testing::{generate, Rng},
+    };
+
+    fn assert_matches_oracle(input: &str) {
+        assert_matches_oracle_with(input, &Options::default());
+    }
+
+    fn assert_matches_oracle_with(input: &str, options: &Options) {
+        assert_eq!(
+            Map::parse(input).regions(options),
+            region::regions_with(input, Cells::Include, options),
+            "map:\n{input}"
+        );
+    }
+
+    #[rstest]
+    #[case("RRRRIICCFF\nRRRRIICCCF\nVVRRRCCFFF\nVVRCCCJFFF\nVVVVCJJCFE\nVVIVCCJJEE\nVVIIICJJEE\nMIIIIIJJEE\nMIIISIJEEE\nMMMISSJEEE\n")]
+    #[case("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA\n")]
+    #[case("AAAAA\nA...A\nA.A.A\nA.AAA\nAAAAA\n")]
+    #[case("AB\nBA\n")]
+    #[case("A\n")]
+    #[case("")]
+    fn test_fixed_maps(#[case] input: &str) {
+        assert_matches_oracle(input);
+    }
+
+    #[test]
+    fn test_noise() {
+        let mut rng = Rng::new(12);
+        for plants in 1..=4 {
+            for _ in 0..200 {
+                let (width, height) = (1 + rng.below(12), 1 + rng.below(12));
+                assert_matches_oracle(&generate::noise(&mut rng, width, height, plants));
+            }
+        }
+    }
+
+    #[test]
+    fn test_enclaves() {
+        let mut rng = Rng::new(13);
+        for _ in 0..300 {
+            let (width, height) = (1 + rng.below(16), 1 + rng.below(16));
+            assert_matches_oracle(&generate::enclaves(&mut rng, width, height));
+        }
+    }
+
+    #[test]
+    fn test_rings_with_holes() {
+        let mut rng = Rng::new(14);
+        for _ in 0..300 {
+            let (width, height) = (1 + rng.below(16), 1 + rng.below(16));
+            assert_matches_oracle(&generate::rings_with_holes(&mut rng, width, height));
+        }
+    }
+
+    #[test]
+    fn test_checkerboards() {
+        let mut rng = Rng::new(15);
+        for _ in 0..300 {
+            let (width, height) = (1 + rng.below(16), 1 + rng.below(16));
+            let size = 1 + rng.below(3);
+            assert_matches_oracle(&generate::checkerboard(&mut rng, width, height, size));
+        }
+    }
+
+    #[rstest]
+    #[case("AB\nBA\n", 4, 2)]
+    #[case("A.A\n.A.\nA.A\n", 9, 2)]
+    fn test_corner_touches(#[case] input: &str, #[case] four: usize, #[case] eight: usize) {
+        for (neighbourhood, expected) in
+            [(Neighbourhood::Four, four), (Neighbourhood::Eight, eight)]
+        {
+            let options = Options {
+                neighbourhood,
+                ..Default::default()
+            };
+            assert_matches_oracle_with(input, &options);
+            assert_eq!(expected, Map::parse(input).regions(&options).len());
+        }
+    }
+
+    #[test]
+    fn test_diagonal_touches() {
+        let mut rng = Rng::new(16);
+        for _ in 0..300 {
+            let (width, height) = (1 + rng.below(16), 1 + rng.below(16));
+            assert_matches_oracle(&generate::diagonals(&mut rng, width, height));
+        }
+    }
+
+    #[test]
+    fn test_eight_connected() {
+        let options = Options {
+            neighbourhood: Neighbourhood::Eight,
+            ..Default::default()
+        };
+        let mut rng = Rng::new(17);
+        for _ in 0..300 {
+            let (width, height) = (1 + rng.below(16), 1 + rng.below(16));
+            let (plants, size) = (1 + rng.below(4), 1 + rng.below(2));
+            let input = match rng.below(3) {
+                0 => generate::noise(&mut rng, width, height, plants),
+                1 => generate::checkerboard(&mut rng, width, height, size),
+                _ => generate::diagonals(&mut rng, width, height),
+            };
+            assert_matches_oracle_with(&input, &options);
+        }
+    }
+
+    #[test]
+    fn test_plant_classes() {
+        let mut rng = Rng::new(18);
+        for plants in [Plants::CaseInsensitive, Plants::classes(["Aab", "B"])] {
+            for neighbourhood in [Neighbourhood::Four, Neighbourhood::Eight] {
+                let options = Options {
+                    neighbourhood,
+                    plants: plants.clone(),
+                };
+                for _ in 0..100 {
+                    let (width, height) = (1 + rng.below(12), 1 + rng.below(12));
+                    let input = generate::noise_over(&mut rng, width, height, "AaBbC");
+                    assert_matches_oracle_with(&input, &options);
+                }
+            }
+        }
+    }
+}
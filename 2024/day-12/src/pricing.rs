@@ -0,0 +1,552 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::region::{Cell, Region};
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum PricingError {
+    #[error("the {0} of a region overflows")]
+    #[diagnostic(help("prices and quantities must stay within the range of the amount type"))]
+    Overflow(&'static str),
+
+    #[error("tier from quantity {min_quantity} has discount {discount}")]
+    #[diagnostic(help("discounts are fractions between 0 and 1, like `0.1` for 10%"))]
+    InvalidDiscount {
+        min_quantity: u64,
+        discount: Decimal,
+    },
+
+    #[error("{name} {price} is negative")]
+    #[diagnostic(help("unit prices and minimum charges must be at least 0"))]
+    NegativePrice { name: String, price: Decimal },
+}
+
+/// What a price is computed in. Arithmetic is checked, `None` meaning it overflowed.
+pub trait Amount: Copy + Default + Ord + fmt::Display {
+    fn checked_add(self, other: Self) -> Option<Self>;
+
+    fn checked_sub(self, other: Self) -> Option<Self>;
+}
+
+impl Amount for u64 {
+    fn checked_add(self, other: Self) -> Option<Self> {
+        u64::checked_add(self, other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        u64::checked_sub(self, other)
+    }
+}
+
+fn sum<A: Amount>(mut amounts: impl Iterator<Item = A>) -> Option<A> {
+    amounts.try_fold(A::default(), A::checked_add)
+}
+
+/// Fixed-point decimal with four places, enough for unit prices and percentage discounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Decimal(i128);
+
+impl Decimal {
+    const SCALE: i128 = 10_000;
+
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(Self::SCALE);
+
+    pub fn from_units(units: u64) -> Self {
+        Self(units as i128 * Self::SCALE)
+    }
+
+    pub fn checked_times(self, quantity: u64) -> Option<Self> {
+        self.0.checked_mul(quantity as i128).map(Self)
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    /// Rounded half away from zero to four places.
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        let product = self.0.checked_mul(other.0)?;
+        let half = Self::SCALE / 2 * product.signum();
+        Some(Self(product.checked_add(half)? / Self::SCALE))
+    }
+}
+
+impl Amount for Decimal {
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Decimal::checked_add(self, other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        Decimal::checked_sub(self, other)
+    }
+}
+
+/// At least two places, more only where they are not zero.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let (magnitude, scale) = (self.0.unsigned_abs(), Self::SCALE as u128);
+        let (units, fraction) = (magnitude / scale, magnitude % scale);
+        let fraction = format!("{fraction:04}");
+        let fraction = fraction.trim_end_matches('0');
+        write!(f, "{sign}{units}.{fraction:0<2}")
+    }
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("invalid decimal `{0}`")]
+#[diagnostic(help("expected digits with at most four decimal places, like `12.5`"))]
+pub struct DecimalError(String);
+
+impl FromStr for Decimal {
+    type Err = DecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || DecimalError(s.to_string());
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        let (units, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if units.is_empty() || fraction.len() > 4 || !all_digits(units) || !all_digits(fraction) {
+            return Err(error());
+        }
+
+        let units = units.parse::<i128>().map_err(|_| error())?;
+        let fraction = format!("{fraction:0<4}")
+            .parse::<i128>()
+            .map_err(|_| error())?;
+        let value = units
+            .checked_mul(Self::SCALE)
+            .and_then(|value| value.checked_add(fraction))
+            .ok_or_else(error)?;
+        Ok(Self(if negative { -value } else { value }))
+    }
+}
+
+/// What the fence of a region is measured by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    Perimeter,
+    Sides,
+}
+
+impl Measure {
+    /// The priced quantity of a region: its area times its fence measure.
+    pub fn quantity(self, region: &Region) -> Result<u64, PricingError> {
+        let measure = match self {
+            Self::Perimeter => region.perimeter,
+            Self::Sides => region.sides,
+        };
+        region
+            .area
+            .checked_mul(measure)
+            .ok_or(PricingError::Overflow("quantity"))
+    }
+}
+
+/// How the price of one region was arrived at: `amount = base - discount + surcharge`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quote<A> {
+    pub quantity: u64,
+    pub base: A,
+    pub discount: A,
+    /// Added to bring the price up to the policy's minimum.
+    pub surcharge: A,
+    pub amount: A,
+}
+
+pub trait PricingPolicy {
+    type Amount: Amount;
+
+    fn quote(&self, region: &Region) -> Result<Quote<Self::Amount>, PricingError>;
+}
+
+/// The AoC prices: area times perimeter for part 1, area times sides for part 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Aoc(pub Measure);
+
+impl Aoc {
+    pub const PART1: Self = Self(Measure::Perimeter);
+    pub const PART2: Self = Self(Measure::Sides);
+}
+
+impl PricingPolicy for Aoc {
+    type Amount = u64;
+
+    fn quote(&self, region: &Region) -> Result<Quote<u64>, PricingError> {
+        let quantity = self.0.quantity(region)?;
+        Ok(Quote {
+            quantity,
+            base: quantity,
+            discount: 0,
+            surcharge: 0,
+            amount: quantity,
+        })
+    }
+}
+
+/// A discount on regions whose quantity reaches `min_quantity`, between 0 and 1 inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tier {
+    pub min_quantity: u64,
+    /// Fraction taken off, e.g. `0.1` for 10%.
+    pub discount: Decimal,
+}
+
+/// Unit prices per plant, the best bulk discount tier a region reaches, and a minimum charge
+/// per region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tiered {
+    measure: Measure,
+    unit_price: Decimal,
+    plant_prices: HashMap<char, Decimal>,
+    tiers: Vec<Tier>,
+    minimum: Decimal,
+}
+
+fn non_negative(name: impl FnOnce() -> String, price: Decimal) -> Result<Decimal, PricingError> {
+    match price < Decimal::ZERO {
+        true => Err(PricingError::NegativePrice {
+            name: name(),
+            price,
+        }),
+        false => Ok(price),
+    }
+}
+
+/// The builder methods taking prices fail if they are negative.
+impl Tiered {
+    pub fn new(measure: Measure, unit_price: Decimal) -> Result<Self, PricingError> {
+        Ok(Self {
+            measure,
+            unit_price: non_negative(|| "unit price".to_string(), unit_price)?,
+            plant_prices: HashMap::new(),
+            tiers: Vec::new(),
+            minimum: Decimal::ZERO,
+        })
+    }
+
+    pub fn plant_price(mut self, plant: char, unit_price: Decimal) -> Result<Self, PricingError> {
+        let unit_price = non_negative(|| format!("unit price of plant {plant}"), unit_price)?;
+        self.plant_prices.insert(plant, unit_price);
+        Ok(self)
+    }
+
+    /// Fails if the discount of `tier` is negative or more than the whole price.
+    pub fn tier(mut self, tier: Tier) -> Result<Self, PricingError> {
+        if !(Decimal::ZERO..=Decimal::ONE).contains(&tier.discount) {
+            return Err(PricingError::InvalidDiscount {
+                min_quantity: tier.min_quantity,
+                discount: tier.discount,
+            });
+        }
+        self.tiers.push(tier);
+        Ok(self)
+    }
+
+    pub fn minimum(mut self, minimum: Decimal) -> Result<Self, PricingError> {
+        self.minimum = non_negative(|| "minimum".to_string(), minimum)?;
+        Ok(self)
+    }
+}
+
+impl PricingPolicy for Tiered {
+    type Amount = Decimal;
+
+    fn quote(&self, region: &Region) -> Result<Quote<Decimal>, PricingError> {
+        let overflow = PricingError::Overflow;
+        let quantity = self.measure.quantity(region)?;
+        let unit_price = self
+            .plant_prices
+            .get(&region.plant)
+            .copied()
+            .unwrap_or(self.unit_price);
+        let base = unit_price
+            .checked_times(quantity)
+            .ok_or(overflow("base price"))?;
+
+        let discount = self
+            .tiers
+            .iter()
+            .filter(|tier| quantity >= tier.min_quantity)
+            .map(|tier| base.checked_mul(tier.discount).ok_or(overflow("discount")))
+            .try_fold(Decimal::ZERO, |best, discount| Ok(best.max(discount?)))?;
+        let discounted = base.checked_sub(discount).ok_or(overflow("discount"))?;
+        let surcharge = self
+            .minimum
+            .checked_sub(discounted)
+            .ok_or(overflow("surcharge"))?
+            .max(Decimal::ZERO);
+
+        Ok(Quote {
+            quantity,
+            base,
+            discount,
+            surcharge,
+            amount: discounted
+                .checked_add(surcharge)
+                .ok_or(overflow("amount"))?,
+        })
+    }
+}
+
+/// The quote of one region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionPrice<A> {
+    pub plant: char,
+    pub origin: Cell,
+    pub quote: Quote<A>,
+}
+
+/// Quotes of every region, in the order of `regions`.
+pub fn breakdown<P: PricingPolicy>(
+    regions: &[Region],
+    policy: &P,
+) -> Result<Vec<RegionPrice<P::Amount>>, PricingError> {
+    regions
+        .iter()
+        .map(|region| {
+            Ok(RegionPrice {
+                plant: region.plant,
+                origin: region.origin,
+                quote: policy.quote(region)?,
+            })
+        })
+        .collect()
+}
+
+pub fn total<P: PricingPolicy>(regions: &[Region], policy: &P) -> Result<P::Amount, PricingError> {
+    let amounts = regions
+        .iter()
+        .map(|region| Ok(policy.quote(region)?.amount))
+        .collect::<Result<Vec<_>, PricingError>>()?;
+    sum(amounts.into_iter()).ok_or(PricingError::Overflow("total"))
+}
+
+pub struct Breakdown<'a, A>(pub &'a [RegionPrice<A>]);
+
+impl<A: Amount> fmt::Display for Breakdown<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for price in self.0 {
+            let quote = &price.quote;
+            writeln!(
+                f,
+                "region {} at {},{}: quantity {}, base {}, discount {}, surcharge {}, amount {}",
+                price.plant,
+                price.origin.0,
+                price.origin.1,
+                quote.quantity,
+                quote.base,
+                quote.discount,
+                quote.surcharge,
+                quote.amount,
+            )?;
+        }
+        match sum(self.0.iter().map(|price| price.quote.amount)) {
+            Some(total) => writeln!(f, "total {total}"),
+            None => writeln!(f, "total overflows"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::region::{self, Cells};
+
+    const EXAMPLE: &str = "AAAA\nBBCD\nBBCC\nEEEC\n";
+
+    fn decimal(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    #[rstest]
+    #[case("0", "0.00")]
+    #[case("12.5", "12.50")]
+    #[case("-0.125", "-0.125")]
+    #[case("3.1416", "3.1416")]
+    fn test_decimal_round_trip(#[case] input: &str, #[case] display: &str) {
+        assert_eq!(display, decimal(input).to_string());
+    }
+
+    #[test]
+    fn test_decimal_display_extremes() {
+        let min = Decimal(i128::MIN);
+        assert_eq!("-17014118346046923173168730371588410.5728", min.to_string());
+        assert_eq!(Some(min), Decimal(i128::MIN + 1).checked_sub(Decimal(1)));
+        assert_eq!(
+            "17014118346046923173168730371588410.5727",
+            Decimal(i128::MAX).to_string()
+        );
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case(".5")]
+    #[case("1.23456")]
+    #[case("1,5")]
+    fn test_decimal_errors(#[case] input: &str) {
+        assert!(input.parse::<Decimal>().is_err());
+    }
+
+    #[test]
+    fn test_decimal_mul_rounds() {
+        let mul = |a, b| decimal(a).checked_mul(decimal(b)).unwrap();
+        assert_eq!(decimal("0.0001"), mul("0.0015", "0.05"));
+        assert_eq!(decimal("-0.0001"), mul("-0.0015", "0.05"));
+        assert_eq!(decimal("14.8"), mul("148", "0.1"));
+    }
+
+    #[test]
+    fn test_decimal_overflow() {
+        let huge = decimal("10000000000000000000000000000000000");
+        assert_eq!(None, huge.checked_mul(decimal("2")));
+        assert_eq!(None, huge.checked_times(2));
+        assert_eq!(None, huge.checked_add(huge));
+        assert_eq!(
+            None,
+            decimal("-1").checked_sub(huge).unwrap().checked_sub(huge)
+        );
+        assert_eq!(Some(decimal("-1")), Decimal::ZERO.checked_sub(Decimal::ONE));
+    }
+
+    #[test]
+    fn test_aoc_presets() {
+        let regions = region::regions(EXAMPLE, Cells::Omit);
+        assert_eq!(140, total(&regions, &Aoc::PART1).unwrap());
+        assert_eq!(80, total(&regions, &Aoc::PART2).unwrap());
+    }
+
+    #[test]
+    fn test_tiered() -> miette::Result<()> {
+        // A: 4 * 10 = 40, B: 4 * 8 = 32, C: 4 * 10 = 40, D: 1 * 4 = 4, E: 3 * 8 = 24
+        let policy = Tiered::new(Measure::Perimeter, decimal("1"))?
+            .plant_price('A', decimal("0.5"))?
+            .tier(Tier {
+                min_quantity: 30,
+                discount: decimal("0.1"),
+            })?
+            .tier(Tier {
+                min_quantity: 40,
+                discount: decimal("0.25"),
+            })?
+            .minimum(decimal("5"))?;
+        let regions = region::regions(EXAMPLE, Cells::Omit);
+
+        let amounts = breakdown(&regions, &policy)?
+            .into_iter()
+            .map(|price| (price.plant, price.quote.amount.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ('A', "15.00".to_string()),
+                ('B', "28.80".to_string()),
+                ('C', "30.00".to_string()),
+                ('D', "5.00".to_string()),
+                ('E', "24.00".to_string()),
+            ],
+            amounts
+        );
+        assert_eq!(decimal("102.8"), total(&regions, &policy)?);
+
+        let d = policy.quote(&regions[3])?;
+        assert_eq!((decimal("4"), decimal("1")), (d.base, d.surcharge));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("-0.1")]
+    #[case("1.0001")]
+    fn test_invalid_tier(#[case] discount: &str) -> miette::Result<()> {
+        let tiered = Tiered::new(Measure::Perimeter, decimal("1"))?.tier(Tier {
+            min_quantity: 30,
+            discount: decimal(discount),
+        });
+        assert!(matches!(
+            tiered,
+            Err(PricingError::InvalidDiscount {
+                min_quantity: 30,
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    fn negative_price(tiered: Result<Tiered, PricingError>) -> Option<String> {
+        match tiered {
+            Err(PricingError::NegativePrice { name, price }) if price == decimal("-0.5") => {
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_negative_unit_price() {
+        let tiered = Tiered::new(Measure::Perimeter, decimal("-0.5"));
+        assert_eq!(Some("unit price".to_string()), negative_price(tiered));
+    }
+
+    #[test]
+    fn test_negative_plant_price() -> miette::Result<()> {
+        let tiered = Tiered::new(Measure::Perimeter, decimal("1"))?
+            .plant_price('B', decimal("0"))?
+            .plant_price('A', decimal("-0.5"));
+        assert_eq!(
+            Some("unit price of plant A".to_string()),
+            negative_price(tiered)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_minimum() -> miette::Result<()> {
+        let tiered = Tiered::new(Measure::Perimeter, decimal("1"))?.minimum(decimal("-0.5"));
+        assert_eq!(Some("minimum".to_string()), negative_price(tiered));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tiered_overflow() -> miette::Result<()> {
+        let regions = region::regions(EXAMPLE, Cells::Omit);
+        let huge = decimal("10000000000000000000000000000000000");
+        let policy = Tiered::new(Measure::Perimeter, huge)?;
+        assert!(matches!(
+            total(&regions, &policy),
+            Err(PricingError::Overflow("base price"))
+        ));
+
+        // every region fits on its own, but not all of them together
+        let policy = Tiered::new(Measure::Perimeter, decimal("1"))?.minimum(huge)?;
+        assert!(breakdown(&regions, &policy).is_ok());
+        assert!(matches!(
+            total(&regions, &policy),
+            Err(PricingError::Overflow("total"))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_breakdown_display() {
+        let regions = region::regions("AB\n", Cells::Omit);
+        let prices = breakdown(&regions, &Aoc::PART2).unwrap();
+        assert_eq!(
+            "region A at 0,0: quantity 4, base 4, discount 0, surcharge 0, amount 4
+region B at 1,0: quantity 4, base 4, discount 0, surcharge 0, amount 4
+total 8
+",
+            Breakdown(&prices).to_string()
+        );
+    }
+}
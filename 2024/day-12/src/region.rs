@@ -0,0 +1,385 @@
+use std::{
+    fmt,
+    io::{self, BufRead},
+    ops::Range,
+    str::FromStr,
+};
+
+use itertools::Itertools;
+use miette::Diagnostic;
+use thiserror::Error;
+
+pub use crate::scanline::Cell;
+use crate::{
+    band,
+    scanline::{self, Accumulator, Area, Chunk, Options, Perimeter, Sides},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BoundingBox {
+    pub x: Range<usize>,
+    pub y: Range<usize>,
+}
+
+impl BoundingBox {
+    fn merge(&mut self, other: &Self) {
+        self.x = self.x.start.min(other.x.start)..self.x.end.max(other.x.end);
+        self.y = self.y.start.min(other.y.start)..self.y.end.max(other.y.end);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub plant: char,
+    /// First cell of the region in reading order.
+    pub origin: Cell,
+    pub area: u64,
+    pub perimeter: u64,
+    pub sides: u64,
+    pub bounds: BoundingBox,
+    /// Member cells in reading order, if they were asked for.
+    pub cells: Option<Vec<Cell>>,
+}
+
+impl Region {
+    pub(crate) fn with_cells(chunk: &Chunk, cells: Cells) -> Self {
+        Self {
+            plant: chunk.plant,
+            origin: (chunk.range.start, chunk.y),
+            area: chunk.range.len() as u64,
+            perimeter: Perimeter::of_chunk(chunk),
+            sides: Sides::of_chunk(chunk),
+            bounds: BoundingBox {
+                x: chunk.range.clone(),
+                y: chunk.y..chunk.y + 1,
+            },
+            cells: match cells {
+                Cells::Omit => None,
+                Cells::Include => Some(chunk.range.clone().map(|x| (x, chunk.y)).collect()),
+            },
+        }
+    }
+
+    pub(crate) fn sort_cells(mut self) -> Self {
+        if let Some(cells) = &mut self.cells {
+            cells.sort_unstable_by_key(|&(x, y)| (y, x));
+        }
+        self
+    }
+}
+
+impl Accumulator for Region {
+    fn new(chunk: &Chunk) -> Self {
+        Self::with_cells(chunk, Cells::Omit)
+    }
+
+    fn add_contact(&mut self, above: &Chunk, below: &Chunk) {
+        self.perimeter -= Perimeter::of_contact(above, below);
+        self.sides -= Sides::of_contact(above, below);
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.area += other.area;
+        self.perimeter += other.perimeter;
+        self.sides += other.sides;
+        self.bounds.merge(&other.bounds);
+        if let (Some(cells), Some(other)) = (&mut self.cells, other.cells) {
+            cells.extend(other);
+        }
+    }
+}
+
+/// Whether [`regions`] lists the member cells of every region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cells {
+    #[default]
+    Omit,
+    Include,
+}
+
+/// Every region of the map with its metrics, in reading order of their origins.
+pub fn regions(input: &str, cells: Cells) -> Vec<Region> {
+    regions_with(input, cells, &Options::default())
+}
+
+/// [`regions`], connecting cells and plants as `options` say.
+pub fn regions_with(input: &str, cells: Cells, options: &Options) -> Vec<Region> {
+    scanline::scan_with(input, options, |chunk| Region::with_cells(chunk, cells))
+        .into_iter()
+        .map(Region::sort_cells)
+        .collect()
+}
+
+/// [`regions_with`], with the map split into bands that are scanned in parallel.
+pub fn regions_par(input: &str, cells: Cells, options: &Options) -> Vec<Region> {
+    band::scan_par_with(input, options, |chunk| Region::with_cells(chunk, cells))
+        .into_iter()
+        .map(Region::sort_cells)
+        .collect()
+}
+
+/// Regions of the map in `reader`, each yielded as soon as the row below it has been read, so
+/// only about one row of the map is held in memory (plus the cells of open regions, if asked for).
+pub fn stream(
+    reader: impl BufRead,
+    cells: Cells,
+    options: Options,
+) -> impl Iterator<Item = io::Result<Region>> {
+    scanline::stream_with(reader, options, move |chunk| {
+        Region::with_cells(chunk, cells)
+    })
+    .map(|region| region.map(|(_, region)| region.sort_cells()))
+}
+
+/// Fence prices of the whole map for both AoC parts, from a single pass.
+pub fn prices(input: &str) -> (u64, u64) {
+    scanline::scan::<(Area, Perimeter, Sides)>(input)
+        .into_iter()
+        .fold(
+            (0, 0),
+            |(by_perimeter, by_sides), (area, perimeter, sides)| {
+                (
+                    by_perimeter + area.0 * perimeter.0,
+                    by_sides + area.0 * sides.0,
+                )
+            },
+        )
+}
+
+/// How the runners print the region inventory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("unknown region format `{0}`")]
+#[diagnostic(help("expected `table` or `json`"))]
+pub struct FormatError(String);
+
+impl FromStr for Format {
+    type Err = FormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            _ => Err(FormatError(s.to_string())),
+        }
+    }
+}
+
+pub struct Table<'a>(pub &'a [Region]);
+
+impl fmt::Display for Table<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<5} {:>9} {:>8} {:>9} {:>8} {:>9} {:>9}",
+            "plant", "origin", "area", "perimeter", "sides", "x", "y"
+        )?;
+        for region in self.0 {
+            writeln!(
+                f,
+                "{:<5} {:>9} {:>8} {:>9} {:>8} {:>9} {:>9}",
+                region.plant,
+                format!("{},{}", region.origin.0, region.origin.1),
+                region.area,
+                region.perimeter,
+                region.sides,
+                format!("{}..{}", region.bounds.x.start, region.bounds.x.end),
+                format!("{}..{}", region.bounds.y.start, region.bounds.y.end),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn json_char(plant: char) -> String {
+    match plant {
+        '"' => r#"\""#.to_string(),
+        '\\' => r"\\".to_string(),
+        plant if plant.is_control() => format!("\\u{:04x}", plant as u32),
+        plant => plant.to_string(),
+    }
+}
+
+/// One JSON array of region objects; `cells` is only present when the cells were collected.
+pub fn write_json(regions: &[Region], mut writer: impl io::Write) -> io::Result<()> {
+    writeln!(writer, "[")?;
+    for (i, region) in regions.iter().enumerate() {
+        write!(
+            writer,
+            r#"  {{"plant": "{}", "origin": [{}, {}], "area": {}, "perimeter": {}, "sides": {}, "bounds": {{"x": [{}, {}], "y": [{}, {}]}}"#,
+            json_char(region.plant),
+            region.origin.0,
+            region.origin.1,
+            region.area,
+            region.perimeter,
+            region.sides,
+            region.bounds.x.start,
+            region.bounds.x.end,
+            region.bounds.y.start,
+            region.bounds.y.end,
+        )?;
+        if let Some(cells) = &region.cells {
+            let cells = cells.iter().map(|(x, y)| format!("[{x}, {y}]")).join(", ");
+            write!(writer, r#", "cells": [{cells}]"#)?;
+        }
+        let separator = if i + 1 < regions.len() { "," } else { "" };
+        writeln!(writer, "}}{separator}")?;
+    }
+    writeln!(writer, "]")
+}
+
+/// Writes the inventory of `input` in `format`.
+pub fn write_inventory(
+    input: &str,
+    format: Format,
+    cells: Cells,
+    options: &Options,
+    mut writer: impl io::Write,
+) -> io::Result<()> {
+    let regions = regions_with(input, cells, options);
+    match format {
+        Format::Table => write!(writer, "{}", Table(&regions)),
+        Format::Json => write_json(&regions, writer),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::scanline::{Neighbourhood, Plants};
+
+    const EXAMPLE: &str = "RRRRIICCFF
+RRRRIICCCF
+VVRRRCCFFF
+VVRCCCJFFF
+VVVVCJJCFE
+VVIVCCJJEE
+VVIIICJJEE
+MIIIIIJJEE
+MIIISIJEEE
+MMMISSJEEE
+";
+
+    #[test]
+    fn test_example_inventory() {
+        let regions = regions(EXAMPLE, Cells::Omit);
+        let summary = regions
+            .iter()
+            .map(|region| (region.plant, region.area, region.perimeter, region.sides))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ('R', 12, 18, 10),
+                ('I', 4, 8, 4),
+                ('C', 14, 28, 22),
+                ('F', 10, 18, 12),
+                ('V', 13, 20, 10),
+                ('J', 11, 20, 12),
+                ('C', 1, 4, 4),
+                ('E', 13, 18, 8),
+                ('I', 14, 22, 16),
+                ('M', 5, 12, 6),
+                ('S', 3, 8, 6),
+            ],
+            summary
+        );
+        assert_eq!(
+            1930,
+            regions.iter().map(|r| r.area * r.perimeter).sum::<u64>()
+        );
+        assert_eq!(1206, regions.iter().map(|r| r.area * r.sides).sum::<u64>());
+    }
+
+    #[rstest]
+    #[case("AAAA\nBBCD\nBBCC\nEEEC\n", 140, 80)]
+    #[case("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO\n", 772, 436)]
+    #[case("EEEEE\nEXXXX\nEEEEE\nEXXXX\nEEEEE\n", 692, 236)]
+    #[case("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA\n", 1184, 368)]
+    // a ring whose left part only joins up after the whole row has been scanned
+    #[case("AAAAA\nA...A\nA.A.A\nA.AAA\nAAAAA\n", 730, 276)]
+    fn test_prices(#[case] input: &str, #[case] perimeter: u64, #[case] sides: u64) {
+        let regions = regions(input, Cells::Omit);
+        assert_eq!(
+            perimeter,
+            regions.iter().map(|r| r.area * r.perimeter).sum::<u64>()
+        );
+        assert_eq!(sides, regions.iter().map(|r| r.area * r.sides).sum::<u64>());
+        assert_eq!((perimeter, sides), prices(input));
+    }
+
+    #[rstest]
+    #[case("A.\n.A\n", Options::default(), "A..A", 16)]
+    #[case("A.\n.A\n", Options { neighbourhood: Neighbourhood::Eight, ..Default::default() }, "A.", 16)]
+    #[case("Aa\naA\n", Options { plants: Plants::CaseInsensitive, ..Default::default() }, "A", 8)]
+    #[case("AB\nba\n", Options { plants: "Ab,Ba".parse().unwrap(), ..Default::default() }, "AB", 12)]
+    fn test_options(
+        #[case] input: &str,
+        #[case] options: Options,
+        #[case] plants: &str,
+        #[case] perimeter: u64,
+    ) {
+        let regions = regions_with(input, Cells::Omit, &options);
+        assert_eq!(
+            plants,
+            regions
+                .iter()
+                .map(|region| region.plant)
+                .collect::<String>()
+        );
+        assert_eq!(
+            perimeter,
+            regions.iter().map(|region| region.perimeter).sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn test_cells_and_bounds() {
+        let regions = regions("AB\nBB\nAB\n", Cells::Include);
+        assert_eq!(3, regions.len());
+
+        let b = &regions[1];
+        assert_eq!(('B', (1, 0)), (b.plant, b.origin));
+        assert_eq!(BoundingBox { x: 0..2, y: 0..3 }, b.bounds);
+        assert_eq!(Some(vec![(1, 0), (0, 1), (1, 1), (1, 2)]), b.cells);
+        assert_eq!((4, 10, 8), (b.area, b.perimeter, b.sides));
+
+        assert_eq!(Some(vec![(0, 2)]), regions[2].cells);
+    }
+
+    #[test]
+    fn test_stream_matches_regions() -> io::Result<()> {
+        let mut streamed = stream(io::Cursor::new(EXAMPLE), Cells::Include, Options::default())
+            .collect::<io::Result<Vec<_>>>()?;
+        streamed.sort_unstable_by_key(|region| (region.origin.1, region.origin.0));
+        assert_eq!(regions(EXAMPLE, Cells::Include), streamed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_json() -> io::Result<()> {
+        let mut json = Vec::new();
+        write_inventory(
+            "A\"\n",
+            Format::Json,
+            Cells::Include,
+            &Options::default(),
+            &mut json,
+        )?;
+        assert_eq!(
+            r#"[
+  {"plant": "A", "origin": [0, 0], "area": 1, "perimeter": 4, "sides": 4, "bounds": {"x": [0, 1], "y": [0, 1]}, "cells": [[0, 0]]},
+  {"plant": "\"", "origin": [1, 0], "area": 1, "perimeter": 4, "sides": 4, "bounds": {"x": [1, 2], "y": [0, 1]}, "cells": [[1, 0]]}
+]
+"#,
+            String::from_utf8(json).unwrap()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,549 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, BufRead},
+    ops::Range,
+    str::FromStr,
+};
+
+use itertools::Itertools;
+use miette::Diagnostic;
+use thiserror::Error;
+
+/// Cells are `(x, y)`, with `y` counting rows down from the top.
+pub type Cell = (usize, usize);
+
+/// A maximal run of one plant within row `y`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub plant: char,
+    pub y: usize,
+    pub range: Range<usize>,
+}
+
+impl Chunk {
+    fn len(&self) -> u64 {
+        self.range.len() as u64
+    }
+
+    /// Cells that `self` and the chunk directly below it share an edge along.
+    fn overlap(&self, below: &Chunk) -> u64 {
+        (self.range.end.min(below.range.end) - self.range.start.max(below.range.start)) as u64
+    }
+}
+
+/// Which cells of the same plant are connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Neighbourhood {
+    /// Cells sharing an edge.
+    #[default]
+    Four,
+    /// Cells sharing an edge or a corner. Fences are unaffected: cells touching only at a
+    /// corner share no fence, and no side runs through such a corner.
+    Eight,
+}
+
+/// Which plants count as the same crop. A region is labelled with the representative of its
+/// class: the upper-case letter, or the first plant of a custom class.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Plants {
+    #[default]
+    Distinct,
+    CaseInsensitive,
+    Classes(HashMap<char, char>),
+}
+
+impl Plants {
+    /// Each group of plants forms one class, represented by the group's first plant.
+    pub fn classes<'a>(groups: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut classes = HashMap::new();
+        for group in groups {
+            if let Some(representative) = group.chars().next() {
+                for plant in group.chars() {
+                    classes.insert(plant, representative);
+                }
+            }
+        }
+        Self::Classes(classes)
+    }
+
+    pub fn class(&self, plant: char) -> char {
+        match self {
+            Self::Distinct => plant,
+            Self::CaseInsensitive => plant.to_ascii_uppercase(),
+            Self::Classes(classes) => classes.get(&plant).copied().unwrap_or(plant),
+        }
+    }
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("empty plant class in `{0}`")]
+#[diagnostic(help("expected `case-insensitive` or comma-separated groups like `Aa,Bb`"))]
+pub struct PlantsError(String);
+
+impl FromStr for Plants {
+    type Err = PlantsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "case-insensitive" => Ok(Self::CaseInsensitive),
+            s if s.split(',').any(str::is_empty) => Err(PlantsError(s.to_string())),
+            s => Ok(Self::classes(s.split(','))),
+        }
+    }
+}
+
+/// How the map is split into regions.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Options {
+    pub neighbourhood: Neighbourhood,
+    pub plants: Plants,
+}
+
+/// What the scanline engine tracks per region. A region starts out as a single chunk, grows by
+/// every chunk found directly below one of its chunks, and may be merged with another region
+/// that turns out to be connected further down. Metrics whose value over a region is a sum over
+/// its chunks and vertical contacts (area, perimeter, sides, ...) fit this directly.
+pub trait Accumulator: Sized {
+    /// A region made of just `chunk`.
+    fn new(chunk: &Chunk) -> Self;
+
+    /// Accounts for `below` sitting directly under `above`, both already part of this region.
+    fn add_contact(&mut self, above: &Chunk, below: &Chunk);
+
+    /// Absorbs a region that was found to be connected. `self` has the earlier origin.
+    fn merge(&mut self, other: Self);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Area(pub u64);
+
+impl Accumulator for Area {
+    fn new(chunk: &Chunk) -> Self {
+        Self(chunk.len())
+    }
+
+    fn add_contact(&mut self, _above: &Chunk, _below: &Chunk) {}
+
+    fn merge(&mut self, other: Self) {
+        self.0 += other.0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Perimeter(pub u64);
+
+impl Perimeter {
+    /// Fence around a lone chunk.
+    pub fn of_chunk(chunk: &Chunk) -> u64 {
+        2 * chunk.len() + 2
+    }
+
+    /// Fence that both chunks counted but that now lies inside the region.
+    pub fn of_contact(above: &Chunk, below: &Chunk) -> u64 {
+        2 * above.overlap(below)
+    }
+}
+
+impl Accumulator for Perimeter {
+    fn new(chunk: &Chunk) -> Self {
+        Self(Self::of_chunk(chunk))
+    }
+
+    fn add_contact(&mut self, above: &Chunk, below: &Chunk) {
+        self.0 -= Self::of_contact(above, below);
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.0 += other.0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sides(pub u64);
+
+impl Sides {
+    /// A lone chunk is a rectangle.
+    pub fn of_chunk(_chunk: &Chunk) -> u64 {
+        4
+    }
+
+    /// Sides that both chunks counted but that continue from one into the other: a vertical
+    /// side on each aligned end, plus the horizontal side of the other chunk it was cut short by.
+    pub fn of_contact(above: &Chunk, below: &Chunk) -> u64 {
+        2 * u64::from(above.range.start == below.range.start)
+            + 2 * u64::from(above.range.end == below.range.end)
+    }
+}
+
+impl Accumulator for Sides {
+    fn new(chunk: &Chunk) -> Self {
+        Self(Self::of_chunk(chunk))
+    }
+
+    fn add_contact(&mut self, above: &Chunk, below: &Chunk) {
+        self.0 -= Self::of_contact(above, below);
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.0 += other.0;
+    }
+}
+
+macro_rules! impl_accumulator_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: Accumulator),+> Accumulator for ($($name,)+) {
+            fn new(chunk: &Chunk) -> Self {
+                ($($name::new(chunk),)+)
+            }
+
+            fn add_contact(&mut self, above: &Chunk, below: &Chunk) {
+                $(self.$index.add_contact(above, below);)+
+            }
+
+            fn merge(&mut self, other: Self) {
+                $(self.$index.merge(other.$index);)+
+            }
+        }
+    };
+}
+
+impl_accumulator_tuple!(A 0, B 1);
+impl_accumulator_tuple!(A 0, B 1, C 2);
+impl_accumulator_tuple!(A 0, B 1, C 2, D 3);
+
+/// A chunk of the previous row, with the union-find node of its region.
+#[derive(Debug, Clone)]
+pub(crate) struct RowChunk {
+    pub(crate) chunk: Chunk,
+    pub(crate) node: usize,
+}
+
+/// Union-find over chunks. A root holds the accumulator of all chunks below it; since chunks
+/// are numbered in reading order, the smaller root is kept so it always has the earlier origin.
+#[derive(Debug)]
+pub(crate) struct Regions<A> {
+    parent: Vec<usize>,
+    region: Vec<Option<A>>,
+    origin: Vec<Cell>,
+}
+
+impl<A: Accumulator> Regions<A> {
+    pub(crate) fn new() -> Self {
+        Self {
+            parent: Vec::new(),
+            region: Vec::new(),
+            origin: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, origin: Cell, region: A) -> usize {
+        self.parent.push(self.parent.len());
+        self.region.push(Some(region));
+        self.origin.push(origin);
+        self.parent.len() - 1
+    }
+
+    fn find(&mut self, mut node: usize) -> usize {
+        while self.parent[node] != node {
+            self.parent[node] = self.parent[self.parent[node]];
+            node = self.parent[node];
+        }
+        node
+    }
+
+    fn union(&mut self, a: usize, b: usize) -> usize {
+        let (a, b) = (self.find(a), self.find(b));
+        let (root, child) = (a.min(b), a.max(b));
+        if root != child {
+            self.parent[child] = root;
+            let child = self.region[child].take().unwrap();
+            self.region[root].as_mut().unwrap().merge(child);
+        }
+        root
+    }
+
+    pub(crate) fn push_chunk(&mut self, chunk: Chunk, new: impl FnOnce(&Chunk) -> A) -> RowChunk {
+        let node = self.push((chunk.range.start, chunk.y), new(&chunk));
+        RowChunk { chunk, node }
+    }
+
+    /// Joins each chunk of `below` to the same-plant chunks of `above` it touches, merging their
+    /// regions where it bridges two of them. Only chunks sharing an edge have a contact to
+    /// account for; chunks that merely touch corners share no fence.
+    pub(crate) fn join(
+        &mut self,
+        above: &[RowChunk],
+        below: &[RowChunk],
+        neighbourhood: Neighbourhood,
+    ) {
+        // the extra cell either side that reaches the chunks touching diagonally
+        let reach = match neighbourhood {
+            Neighbourhood::Four => 0,
+            Neighbourhood::Eight => 1,
+        };
+        let mut above = above.iter().peekable();
+        for chunk in below {
+            let range = &chunk.chunk.range;
+            while above
+                .next_if(|prev| prev.chunk.range.end + reach <= range.start)
+                .is_some()
+            {}
+            for prev in above
+                .clone()
+                .take_while(|prev| prev.chunk.range.start < range.end + reach)
+                .filter(|prev| prev.chunk.plant == chunk.chunk.plant)
+            {
+                let root = self.union(chunk.node, prev.node);
+                if prev.chunk.range.start < range.end && range.start < prev.chunk.range.end {
+                    self.region[root]
+                        .as_mut()
+                        .unwrap()
+                        .add_contact(&prev.chunk, &chunk.chunk);
+                }
+            }
+        }
+    }
+
+    /// Moves all nodes of `other` after those of `self`, returning by how much they shifted.
+    pub(crate) fn append(&mut self, other: Self) -> usize {
+        let offset = self.parent.len();
+        self.parent
+            .extend(other.parent.into_iter().map(|parent| parent + offset));
+        self.region.extend(other.region);
+        self.origin.extend(other.origin);
+        offset
+    }
+
+    pub(crate) fn into_regions(self) -> impl Iterator<Item = A> {
+        self.region.into_iter().flatten()
+    }
+}
+
+/// Splits a row into its chunks, each labelled with its class of plants.
+pub fn chunks<'a>(y: usize, line: &'a str, plants: &'a Plants) -> impl Iterator<Item = Chunk> + 'a {
+    line.chars()
+        .enumerate()
+        .chunk_by(|&(_, plant)| plants.class(plant))
+        .into_iter()
+        .map(|(plant, chunk)| {
+            let mut chunk = chunk.map(|(x, _)| x);
+            let start = chunk.next().unwrap();
+            Chunk {
+                plant,
+                y,
+                range: start..chunk.last().unwrap_or(start) + 1,
+            }
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// The scanline engine, fed one row at a time. It only keeps the regions that still have a chunk
+/// in the last row, so its memory depends on the width of the map but not on its height.
+///
+/// Each chunk is joined to the same-plant chunks above it that it shares an edge with, merging
+/// their regions where it bridges two of them. Consider this region while processing:
+///
+/// ```text
+/// ..AAAAAAAAAA........
+/// ..A.......AAAAAAAAAA
+/// ..A.AAAAA.A........A
+/// ..A.A.A.A.A.AAAA...A
+/// .AAAAAAAAAA.A.AAAAAA
+/// ```
+pub struct Scanner<A, F> {
+    options: Options,
+    new: F,
+    y: usize,
+    prev_row: Vec<RowChunk>,
+    regions: Regions<A>,
+}
+
+impl<A: Accumulator, F: FnMut(&Chunk) -> A> Scanner<A, F> {
+    /// Starts each region with `new`, usually [`Accumulator::new`].
+    pub fn new(options: Options, new: F) -> Self {
+        Self {
+            options,
+            new,
+            y: 0,
+            prev_row: Vec::new(),
+            regions: Regions::new(),
+        }
+    }
+
+    /// Scans the next row, returning the regions it finished: those that had a chunk in the row
+    /// before but have none in this one, in reading order of their first cells.
+    pub fn push_row(&mut self, line: &str) -> Vec<(Cell, A)> {
+        let row = chunks(self.y, line, &self.options.plants)
+            .map(|chunk| self.regions.push_chunk(chunk, &mut self.new))
+            .collect::<Vec<_>>();
+        self.regions
+            .join(&self.prev_row, &row, self.options.neighbourhood);
+
+        self.y += 1;
+        self.prev_row = row;
+        self.compact()
+    }
+
+    /// Ends the map, returning the regions that were still open.
+    pub fn finish(mut self) -> Vec<(Cell, A)> {
+        self.prev_row.clear();
+        self.compact()
+    }
+
+    /// Renumbers the regions of the last row from zero, keeping their order and so the smaller
+    /// root invariant, and hands back every other region since nothing can reach it any more.
+    fn compact(&mut self) -> Vec<(Cell, A)> {
+        let mut live = vec![false; self.regions.parent.len()];
+        for chunk in &mut self.prev_row {
+            chunk.node = self.regions.find(chunk.node);
+            live[chunk.node] = true;
+        }
+
+        let mut regions = Regions::new();
+        let mut renumbered = vec![usize::MAX; live.len()];
+        let mut finished = Vec::new();
+        let old = std::mem::replace(&mut self.regions, Regions::new());
+        for (node, (region, origin)) in old.region.into_iter().zip(old.origin).enumerate() {
+            let Some(region) = region else { continue };
+            if live[node] {
+                renumbered[node] = regions.push(origin, region);
+            } else {
+                finished.push((origin, region));
+            }
+        }
+        for chunk in &mut self.prev_row {
+            chunk.node = renumbered[chunk.node];
+        }
+        self.regions = regions;
+        finished
+    }
+}
+
+/// Every region of the map, in reading order of their first cells.
+pub fn scan<A: Accumulator>(input: &str) -> Vec<A> {
+    scan_with(input, &Options::default(), A::new)
+}
+
+/// [`scan`] with `options`, starting each region with `new` instead of [`Accumulator::new`].
+pub fn scan_with<A: Accumulator>(
+    input: &str,
+    options: &Options,
+    new: impl FnMut(&Chunk) -> A,
+) -> Vec<A> {
+    let mut scanner = Scanner::new(options.clone(), new);
+    let mut regions = Vec::new();
+    for line in input.lines() {
+        regions.extend(scanner.push_row(line));
+    }
+    regions.extend(scanner.finish());
+
+    regions.sort_unstable_by_key(|&((x, y), _)| (y, x));
+    regions.into_iter().map(|(_, region)| region).collect()
+}
+
+/// Regions of a map read line by line, each yielded as soon as the row below it is read.
+pub struct Stream<R, A, F> {
+    lines: io::Lines<R>,
+    scanner: Option<Scanner<A, F>>,
+    pending: VecDeque<(Cell, A)>,
+}
+
+impl<R, A, F> Iterator for Stream<R, A, F>
+where
+    R: BufRead,
+    A: Accumulator,
+    F: FnMut(&Chunk) -> A,
+{
+    type Item = io::Result<(Cell, A)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(region) = self.pending.pop_front() {
+                return Some(Ok(region));
+            }
+            let scanner = self.scanner.as_mut()?;
+            match self.lines.next() {
+                Some(Ok(line)) => self.pending.extend(scanner.push_row(&line)),
+                Some(Err(error)) => {
+                    self.scanner = None;
+                    return Some(Err(error));
+                }
+                None => self.pending.extend(self.scanner.take()?.finish()),
+            }
+        }
+    }
+}
+
+/// Streams the regions of the map in `reader` with their origins, in the order they are
+/// finished rather than in reading order.
+pub fn stream<R: BufRead, A: Accumulator>(reader: R) -> Stream<R, A, fn(&Chunk) -> A> {
+    stream_with(reader, Options::default(), A::new)
+}
+
+/// [`stream`] with `options`, starting each region with `new` instead of [`Accumulator::new`].
+pub fn stream_with<R: BufRead, A: Accumulator, F: FnMut(&Chunk) -> A>(
+    reader: R,
+    options: Options,
+    new: F,
+) -> Stream<R, A, F> {
+    Stream {
+        lines: reader.lines(),
+        scanner: Some(Scanner::new(options, new)),
+        pending: VecDeque::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_joined_after_row() {
+        // the left arm only joins the ring once the last row has been scanned
+        let input = "AAAAA\nA...A\nA.A.A\nA.AAA\nAAAAA\n";
+        let regions = scan::<(Area, Perimeter, Sides)>(input);
+        assert_eq!(
+            vec![
+                (Area(19), Perimeter(34), Sides(12)),
+                (Area(6), Perimeter(14), Sides(8)),
+            ],
+            regions
+        );
+    }
+
+    #[test]
+    fn test_regions_finished_early() {
+        let mut scanner = Scanner::new(Options::default(), Area::new);
+        assert_eq!(Vec::<(Cell, Area)>::new(), scanner.push_row("AAB"));
+        // the first row's `B` does not continue, the `A`s do
+        assert_eq!(vec![((2, 0), Area(1))], scanner.push_row("AAC"));
+        assert_eq!(vec![((0, 0), Area(4))], scanner.push_row("DCC"));
+        assert_eq!(vec![((2, 1), Area(3)), ((0, 2), Area(1))], scanner.finish());
+    }
+
+    #[test]
+    fn test_memory_bounded_by_width() {
+        // tall stripes of alternating plants, each finished when the next one starts
+        let mut scanner = Scanner::new(Options::default(), Area::new);
+        let mut finished = 0;
+        for y in 0..1000 {
+            let row = if y / 10 % 2 == 0 { "ABAB" } else { "BABA" };
+            finished += scanner.push_row(row).len();
+            assert!(scanner.regions.parent.len() <= 4);
+        }
+        finished += scanner.finish().len();
+        assert_eq!(400, finished);
+    }
+
+    #[test]
+    fn test_stream() -> io::Result<()> {
+        let input = "AAAAA\r\nA...A\r\nA.A.A\r\nA.AAA\r\nAAAAA";
+        let mut streamed = stream::<_, (Area, Sides)>(io::Cursor::new(input))
+            .map(|region| region.map(|(_, region)| region))
+            .collect::<io::Result<Vec<_>>>()?;
+        streamed.sort_unstable_by_key(|&(area, _)| area.0);
+        assert_eq!(vec![(Area(6), Sides(8)), (Area(19), Sides(12))], streamed);
+        Ok(())
+    }
+}
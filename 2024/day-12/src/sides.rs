@@ -0,0 +1,355 @@
+//! Where the sides of a region are: every straight run of fence as an oriented segment, so a
+//! side count that disagrees with expectations can be inspected.
+
+use std::{collections::HashSet, fmt, io, str::FromStr};
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::{
+    region::{self, json_char, Cell, Cells, Region},
+    scanline::Options,
+};
+
+/// Which side of its cells a segment of fence runs along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Facing {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Facing {
+    pub const ALL: [Self; 4] = [Self::North, Self::East, Self::South, Self::West];
+
+    /// Offset to the cell on the other side of the fence.
+    fn outwards(self) -> (isize, isize) {
+        match self {
+            Self::North => (0, -1),
+            Self::East => (1, 0),
+            Self::South => (0, 1),
+            Self::West => (-1, 0),
+        }
+    }
+
+    fn horizontal(self) -> bool {
+        matches!(self, Self::North | Self::South)
+    }
+}
+
+impl fmt::Display for Facing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letter = match self {
+            Self::North => "N",
+            Self::East => "E",
+            Self::South => "S",
+            Self::West => "W",
+        };
+        f.write_str(letter)
+    }
+}
+
+/// One side of a region: the fence along `facing` of the cells from `start` to `end`, both
+/// inclusive, with `start` first in reading order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub start: Cell,
+    pub end: Cell,
+    pub facing: Facing,
+}
+
+impl Segment {
+    /// How many cells the segment runs along, which is also its length of fence.
+    pub fn length(&self) -> usize {
+        (self.end.0 - self.start.0) + (self.end.1 - self.start.1) + 1
+    }
+}
+
+/// The sides of the region made of `cells`, ordered by start cell in reading order and then by
+/// facing. Cells on either side of a corner where two fences cross start separate segments,
+/// because the fences face opposite ways.
+pub fn segments(cells: &[Cell]) -> Vec<Segment> {
+    let members = cells.iter().copied().collect::<HashSet<_>>();
+    let inside = |(x, y): Cell, (dx, dy): (isize, isize)| {
+        let neighbour = (x.checked_add_signed(dx), y.checked_add_signed(dy));
+        matches!(neighbour, (Some(x), Some(y)) if members.contains(&(x, y)))
+    };
+
+    // fenced cells keyed by facing, the line the fence lies on, then the position along it
+    let mut fenced = cells
+        .iter()
+        .flat_map(|&cell| {
+            Facing::ALL
+                .into_iter()
+                .filter(move |facing| !inside(cell, facing.outwards()))
+                .map(move |facing| match facing.horizontal() {
+                    true => (facing, cell.1, cell.0),
+                    false => (facing, cell.0, cell.1),
+                })
+        })
+        .collect::<Vec<_>>();
+    fenced.sort_unstable();
+
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut previous = None;
+    for (facing, line, position) in fenced {
+        let cell = match facing.horizontal() {
+            true => (position, line),
+            false => (line, position),
+        };
+        match segments.last_mut() {
+            Some(segment) if previous == Some((facing, line, position.wrapping_sub(1))) => {
+                segment.end = cell;
+            }
+            _ => segments.push(Segment {
+                start: cell,
+                end: cell,
+                facing,
+            }),
+        }
+        previous = Some((facing, line, position));
+    }
+
+    segments.sort_unstable_by_key(|segment| (segment.start.1, segment.start.0, segment.facing));
+    segments
+}
+
+/// Every region of the map with its sides.
+pub fn region_sides(input: &str, options: &Options) -> Vec<(Region, Vec<Segment>)> {
+    region::regions_with(input, Cells::Include, options)
+        .into_iter()
+        .map(|region| {
+            let segments = segments(region.cells.as_deref().unwrap_or_default());
+            (region, segments)
+        })
+        .collect()
+}
+
+/// How the runner exports the sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Svg,
+    Json,
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("unknown sides format `{0}`")]
+#[diagnostic(help("expected `svg` or `json`"))]
+pub struct FormatError(String);
+
+impl FromStr for Format {
+    type Err = FormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "svg" => Ok(Self::Svg),
+            "json" => Ok(Self::Json),
+            _ => Err(FormatError(s.to_string())),
+        }
+    }
+}
+
+/// One JSON array of regions, each with its segments as `start`, `end` and `facing`.
+pub fn write_json(sides: &[(Region, Vec<Segment>)], mut writer: impl io::Write) -> io::Result<()> {
+    writeln!(writer, "[")?;
+    for (i, (region, segments)) in sides.iter().enumerate() {
+        writeln!(
+            writer,
+            r#"  {{"plant": "{}", "origin": [{}, {}], "sides": ["#,
+            json_char(region.plant),
+            region.origin.0,
+            region.origin.1,
+        )?;
+        for (j, segment) in segments.iter().enumerate() {
+            let separator = if j + 1 < segments.len() { "," } else { "" };
+            writeln!(
+                writer,
+                r#"    {{"start": [{}, {}], "end": [{}, {}], "facing": "{}"}}{separator}"#,
+                segment.start.0, segment.start.1, segment.end.0, segment.end.1, segment.facing,
+            )?;
+        }
+        let separator = if i + 1 < sides.len() { "," } else { "" };
+        writeln!(writer, "  ]}}{separator}")?;
+    }
+    writeln!(writer, "]")
+}
+
+/// Pixels per cell in the SVG export.
+const CELL: usize = 16;
+/// How far segments are drawn inside their region, so the fences of two neighbouring regions
+/// do not cover each other.
+const INSET: usize = 3;
+
+fn escape_xml(plant: char) -> String {
+    match plant {
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '&' => "&amp;".to_string(),
+        plant => plant.to_string(),
+    }
+}
+
+/// An SVG drawing of every segment, coloured by facing, with the plant and segment as tooltip.
+pub fn write_svg(sides: &[(Region, Vec<Segment>)], mut writer: impl io::Write) -> io::Result<()> {
+    let width = sides.iter().map(|(r, _)| r.bounds.x.end).max().unwrap_or(0);
+    let height = sides.iter().map(|(r, _)| r.bounds.y.end).max().unwrap_or(0);
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        width * CELL,
+        height * CELL,
+    )?;
+    writeln!(
+        writer,
+        "  <style>line {{ stroke-width: 2; stroke-linecap: round }} \
+         .N {{ stroke: #d62728 }} .E {{ stroke: #2ca02c }} \
+         .S {{ stroke: #1f77b4 }} .W {{ stroke: #ff7f0e }}</style>"
+    )?;
+    for (region, segments) in sides {
+        for segment in segments {
+            let (left, top) = (segment.start.0 * CELL, segment.start.1 * CELL);
+            let (right, bottom) = ((segment.end.0 + 1) * CELL, (segment.end.1 + 1) * CELL);
+            let (x1, y1, x2, y2) = match segment.facing {
+                Facing::North => (left, top + INSET, right, top + INSET),
+                Facing::South => (left, bottom - INSET, right, bottom - INSET),
+                Facing::West => (left + INSET, top, left + INSET, bottom),
+                Facing::East => (right - INSET, top, right - INSET, bottom),
+            };
+            writeln!(
+                writer,
+                r#"  <line class="{}" x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}"><title>{} {},{} to {},{} facing {}</title></line>"#,
+                segment.facing,
+                escape_xml(region.plant),
+                segment.start.0,
+                segment.start.1,
+                segment.end.0,
+                segment.end.1,
+                segment.facing,
+            )?;
+        }
+    }
+    writeln!(writer, "</svg>")
+}
+
+/// Writes the sides of every region of `input` in `format`.
+pub fn write_sides(
+    input: &str,
+    format: Format,
+    options: &Options,
+    writer: impl io::Write,
+) -> io::Result<()> {
+    let sides = region_sides(input, options);
+    match format {
+        Format::Svg => write_svg(&sides, writer),
+        Format::Json => write_json(&sides, writer),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scanline::Neighbourhood,
+        testing::{generate, Rng},
+    };
+
+    fn segment(start: Cell, end: Cell, facing: Facing) -> Segment {
+        Segment { start, end, facing }
+    }
+
+    #[test]
+    fn test_l_shape() {
+        // AA
+        // A.
+        let sides = region_sides("AA\nA.\n", &Options::default());
+        assert_eq!(
+            vec![
+                segment((0, 0), (1, 0), Facing::North),
+                segment((0, 0), (0, 1), Facing::West),
+                segment((1, 0), (1, 0), Facing::East),
+                segment((1, 0), (1, 0), Facing::South),
+                segment((0, 1), (0, 1), Facing::East),
+                segment((0, 1), (0, 1), Facing::South),
+            ],
+            sides[0].1
+        );
+        assert_eq!(sides[0].0.sides, sides[0].1.len() as u64);
+        assert_eq!(2, sides[0].1[0].length());
+    }
+
+    #[test]
+    fn test_crossing_fences_are_separate_sides() {
+        let input = "AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA\n";
+        let sides = region_sides(input, &Options::default());
+        let a = &sides[0].1;
+        assert_eq!(12, a.len());
+        // the fences meeting at the corner shared by both B regions
+        assert!(a.contains(&segment((1, 2), (2, 2), Facing::South)));
+        assert!(a.contains(&segment((3, 3), (4, 3), Facing::North)));
+    }
+
+    #[test]
+    fn test_segments_match_side_counts() {
+        let mut rng = Rng::new(45);
+        for _ in 0..300 {
+            let (width, height) = (1 + rng.below(14), 1 + rng.below(14));
+            let input = match rng.below(4) {
+                0 => generate::noise(&mut rng, width, height, 3),
+                1 => generate::enclaves(&mut rng, width, height),
+                2 => generate::rings_with_holes(&mut rng, width, height),
+                _ => generate::diagonals(&mut rng, width, height),
+            };
+            let options = Options {
+                neighbourhood: [Neighbourhood::Four, Neighbourhood::Eight][rng.below(2)],
+                ..Default::default()
+            };
+            for (region, segments) in region_sides(&input, &options) {
+                assert_eq!(region.sides, segments.len() as u64, "map:\n{input}");
+                let fence = segments.iter().map(Segment::length).sum::<usize>();
+                assert_eq!(region.perimeter, fence as u64, "map:\n{input}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_json() -> io::Result<()> {
+        let mut json = Vec::new();
+        write_sides("AB\n", Format::Json, &Options::default(), &mut json)?;
+        assert_eq!(
+            r#"[
+  {"plant": "A", "origin": [0, 0], "sides": [
+    {"start": [0, 0], "end": [0, 0], "facing": "N"},
+    {"start": [0, 0], "end": [0, 0], "facing": "E"},
+    {"start": [0, 0], "end": [0, 0], "facing": "S"},
+    {"start": [0, 0], "end": [0, 0], "facing": "W"}
+  ]},
+  {"plant": "B", "origin": [1, 0], "sides": [
+    {"start": [1, 0], "end": [1, 0], "facing": "N"},
+    {"start": [1, 0], "end": [1, 0], "facing": "E"},
+    {"start": [1, 0], "end": [1, 0], "facing": "S"},
+    {"start": [1, 0], "end": [1, 0], "facing": "W"}
+  ]}
+]
+"#,
+            String::from_utf8(json).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_svg() -> io::Result<()> {
+        let mut svg = Vec::new();
+        write_sides("AA\n", Format::Svg, &Options::default(), &mut svg)?;
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="16""#)
+        );
+        assert_eq!(4, svg.matches("<line ").count());
+        assert!(svg.contains(
+            r#"<line class="N" x1="0" y1="3" x2="32" y2="3"><title>A 0,0 to 1,0 facing N</title></line>"#
+        ));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        Ok(())
+    }
+}
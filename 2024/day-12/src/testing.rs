@@ -0,0 +1,136 @@
+//! Reproducible maps for the stress tests and benchmarks.
+
+/// Small xorshift generator, so the stress tests are reproducible without extra dependencies.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Map generators for the shapes the side counting is most likely to get wrong.
+pub mod generate {
+    use super::Rng;
+
+    fn render(grid: &[Vec<char>]) -> String {
+        grid.iter()
+            .map(|row| row.iter().collect::<String>() + "\n")
+            .collect()
+    }
+
+    /// Uniform noise over the first `plants` letters.
+    pub fn noise(rng: &mut Rng, width: usize, height: usize, plants: usize) -> String {
+        noise_over(rng, width, height, &"ABCDEFGH"[..plants])
+    }
+
+    /// Uniform noise over `plants`.
+    pub fn noise_over(rng: &mut Rng, width: usize, height: usize, plants: &str) -> String {
+        let plants = plants.chars().collect::<Vec<_>>();
+        let grid = (0..height)
+            .map(|_| {
+                (0..width)
+                    .map(|_| plants[rng.below(plants.len())])
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        render(&grid)
+    }
+
+    /// Random rectangles painted over each other, which leaves regions nested inside regions.
+    pub fn enclaves(rng: &mut Rng, width: usize, height: usize) -> String {
+        let mut grid = vec![vec!['A'; width]; height];
+        for plant in "BCDEBCDE".chars() {
+            let (x0, y0) = (rng.below(width), rng.below(height));
+            let (x1, y1) = (
+                x0 + 1 + rng.below(width - x0),
+                y0 + 1 + rng.below(height - y0),
+            );
+            for row in &mut grid[y0..y1] {
+                row[x0..x1].fill(plant);
+            }
+        }
+        render(&grid)
+    }
+
+    /// Concentric rings of alternating plants, with random cells knocked out as holes.
+    pub fn rings_with_holes(rng: &mut Rng, width: usize, height: usize) -> String {
+        let mut grid = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        let depth = x.min(y).min(width - 1 - x).min(height - 1 - y);
+                        ['A', 'B'][depth % 2]
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for _ in 0..(width * height) / 8 {
+            grid[rng.below(height)][rng.below(width)] = 'C';
+        }
+        render(&grid)
+    }
+
+    /// A checkerboard of `size`-sized squares, with a few squares flipped.
+    pub fn checkerboard(rng: &mut Rng, width: usize, height: usize, size: usize) -> String {
+        let mut grid = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| ['A', 'B'][(x / size + y / size) % 2])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for _ in 0..rng.below(4) {
+            let (x, y) = (rng.below(width), rng.below(height));
+            grid[y][x] = if grid[y][x] == 'A' { 'B' } else { 'A' };
+        }
+        render(&grid)
+    }
+
+    /// Blobs: each cell mostly repeats its left or upper neighbour, and is otherwise one of the
+    /// first `plants` letters.
+    pub fn blobs(rng: &mut Rng, width: usize, height: usize, plants: usize) -> String {
+        let letters = "ABCDEFGH".chars().collect::<Vec<_>>();
+        let mut grid: Vec<Vec<char>> = Vec::with_capacity(height);
+        for y in 0..height {
+            let mut row = Vec::with_capacity(width);
+            for x in 0..width {
+                let plant = match rng.below(8) {
+                    0..=2 if x > 0 => row[x - 1],
+                    3..=5 if y > 0 => grid[y - 1][x],
+                    _ => letters[rng.below(plants)],
+                };
+                row.push(plant);
+            }
+            grid.push(row);
+        }
+        render(&grid)
+    }
+
+    /// Random diagonal strokes on a background. Strokes may cross and share edges, so regions
+    /// meet along sides as well as at corners.
+    pub fn diagonals(rng: &mut Rng, width: usize, height: usize) -> String {
+        let mut grid = vec![vec!['.'; width]; height];
+        for plant in "ABAB".chars() {
+            let (mut x, mut y) = (rng.below(width), rng.below(height));
+            let dx = if rng.below(2) == 0 { 1 } else { width - 1 };
+            for _ in 0..width.max(height) {
+                grid[y][x] = plant;
+                x = (x + dx) % width;
+                y = (y + 1) % height;
+            }
+        }
+        render(&grid)
+    }
+}
@@ -1,4 +1,7 @@
-use day_13::part1::process;
+use day_13::{
+    part1::{process, report},
+    report::Summary,
+};
 use miette::Context;
 
 #[tracing::instrument]
@@ -6,7 +9,15 @@ fn main() -> miette::Result<()> {
     tracing_subscriber::fmt::init();
 
     let file = include_str!("../../input.txt");
+
+    // `--report` prints the outcome of each machine and the total instead
+    if std::env::args().any(|arg| arg == "--report") {
+        let outcomes = report(file).context("report part 1")?;
+        print!("{}", Summary(&outcomes));
+        return Ok(());
+    }
+
     let result = process(file).context("process part 1")?;
     print!("{}", result);
     Ok(())
-}
\ No newline at end of file
+}
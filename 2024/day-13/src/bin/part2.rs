@@ -1,4 +1,7 @@
-use day_13::part2::process;
+use day_13::{
+    part2::{process, report},
+    report::Summary,
+};
 use miette::Context;
 
 #[tracing::instrument]
@@ -6,6 +9,14 @@ fn main() -> miette::Result<()> {
     tracing_subscriber::fmt::init();
 
     let file = include_str!("../../input.txt");
+
+    // `--report` prints the outcome of each machine and the total instead
+    if std::env::args().any(|arg| arg == "--report") {
+        let outcomes = report(file).context("report part 2")?;
+        print!("{}", Summary(&outcomes));
+        return Ok(());
+    }
+
     let result = process(file).context("process part 2")?;
     print!("{}", result);
     Ok(())
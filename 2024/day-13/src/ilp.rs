@@ -0,0 +1,357 @@
+//! Cheapest presses for machines with any number of buttons, exactly: branch and bound over the
+//! press counts, bounding each branch by its linear relaxation. With only two equations, that
+//! relaxation is cheapest at a basic solution with at most two buttons strictly between their
+//! bounds, so it is solved exactly by trying every such solution in rational arithmetic.
+
+use crate::machine::{
+    checked, cost, cross, div_ceil, div_floor, Button, Solution, SolveError, Vector,
+};
+
+/// Branches explored before giving up; only machines whose presses are not bounded by either
+/// the limit or the cheapest solution found so far can get this far.
+pub const MAX_BRANCHES: usize = 100_000;
+
+/// Bounds on how often each button is pressed in one branch.
+#[derive(Debug, Clone)]
+struct Branch {
+    low: Vec<i128>,
+    high: Vec<Option<i128>>,
+}
+
+/// A solution of the relaxation: button `i` pressed `numerators[i] / denominator` times, for
+/// `cost / denominator` tokens. The denominator is positive.
+#[derive(Debug)]
+struct Relaxed {
+    numerators: Vec<i128>,
+    denominator: i128,
+    cost: i128,
+}
+
+impl Relaxed {
+    fn fractional(&self) -> Option<usize> {
+        self.numerators
+            .iter()
+            .position(|numerator| numerator % self.denominator != 0)
+    }
+
+    fn cheaper_than(&self, other: &Self) -> Result<bool, SolveError> {
+        let step = "relaxed cost";
+        let this = checked(self.cost.checked_mul(other.denominator), step)?;
+        let that = checked(other.cost.checked_mul(self.denominator), step)?;
+        // on ties, whole presses end the branch without splitting it further
+        Ok(this < that || (this == that && self.fractional().is_none()))
+    }
+}
+
+/// The cheapest presses of `buttons` that reach `prize`, pressing each at most `limit` times.
+pub fn minimise(
+    buttons: &[Button],
+    prize: Vector,
+    limit: Option<i128>,
+) -> Result<Option<Solution>, SolveError> {
+    let mut best: Option<Solution> = None;
+    let mut branches = vec![Branch {
+        low: vec![0; buttons.len()],
+        high: vec![limit; buttons.len()],
+    }];
+    let mut explored = 0;
+    while let Some(branch) = branches.pop() {
+        explored += 1;
+        if explored > MAX_BRANCHES {
+            return Err(SolveError::Exhausted(MAX_BRANCHES));
+        }
+        let Some(relaxed) = relax(buttons, prize, &branch)? else {
+            continue;
+        };
+
+        // costs are whole tokens, so a branch only beats the best with its bound rounded up
+        let bound = checked(div_ceil(relaxed.cost, relaxed.denominator), "relaxed cost")?;
+        if best.as_ref().is_some_and(|best| bound >= best.cost) {
+            continue;
+        }
+
+        let Some(i) = relaxed.fractional() else {
+            let presses = relaxed
+                .numerators
+                .iter()
+                .map(|numerator| numerator / relaxed.denominator)
+                .collect::<Vec<_>>();
+            let cost = cost(buttons, &presses)?;
+            best = Some(Solution { presses, cost });
+            continue;
+        };
+        let floor = checked(
+            div_floor(relaxed.numerators[i], relaxed.denominator),
+            "presses",
+        )?;
+        let mut up = branch.clone();
+        up.low[i] = floor + 1;
+        let mut down = branch;
+        down.high[i] = Some(floor);
+        branches.push(up);
+        branches.push(down);
+    }
+    Ok(best)
+}
+
+/// The cheapest solution of the relaxation of `branch`, if it has any.
+fn relax(
+    buttons: &[Button],
+    prize: Vector,
+    branch: &Branch,
+) -> Result<Option<Relaxed>, SolveError> {
+    let n = buttons.len();
+    let pairs = (0..n).flat_map(|i| (i + 1..n).map(move |j| vec![i, j]));
+    let bases = pairs.chain((0..n).map(|i| vec![i])).chain([vec![]]);
+
+    let mut best: Option<Relaxed> = None;
+    for basis in bases {
+        let others = (0..n).filter(|i| !basis.contains(i)).collect::<Vec<_>>();
+        // every other button sits at its lower or, if it has one, its upper bound
+        'bounds: for mask in 0..1usize << others.len() {
+            let mut values = branch.low.clone();
+            for (bit, &k) in others.iter().enumerate() {
+                if mask >> bit & 1 == 1 {
+                    match branch.high[k] {
+                        Some(high) => values[k] = high,
+                        None => continue 'bounds,
+                    }
+                }
+            }
+            let Some(relaxed) = basic_solution(buttons, prize, branch, &basis, &others, &values)?
+            else {
+                continue;
+            };
+            let cheaper = match &best {
+                Some(best) => relaxed.cheaper_than(best)?,
+                None => true,
+            };
+            if cheaper {
+                best = Some(relaxed);
+            }
+        }
+    }
+    Ok(best)
+}
+
+/// The solution with the buttons outside `basis` pressed as `values` says, if the buttons in
+/// `basis` make up the rest of the way to the prize within their bounds.
+fn basic_solution(
+    buttons: &[Button],
+    prize: Vector,
+    branch: &Branch,
+    basis: &[usize],
+    others: &[usize],
+    values: &[i128],
+) -> Result<Option<Relaxed>, SolveError> {
+    let step = "relaxed presses";
+    let mut rest = prize;
+    for &k in others {
+        let (x, y) = buttons[k].delta;
+        rest.0 = checked(
+            x.checked_mul(values[k]).and_then(|x| rest.0.checked_sub(x)),
+            step,
+        )?;
+        rest.1 = checked(
+            y.checked_mul(values[k]).and_then(|y| rest.1.checked_sub(y)),
+            step,
+        )?;
+    }
+
+    let (mut basic, mut denominator) = match *basis {
+        [i, j] => {
+            let (u, v) = (buttons[i].delta, buttons[j].delta);
+            let determinant = cross(u, v, step)?;
+            if determinant == 0 {
+                return Ok(None);
+            }
+            (
+                vec![(i, cross(rest, v, step)?), (j, cross(u, rest, step)?)],
+                determinant,
+            )
+        }
+        [i] => {
+            let u = buttons[i].delta;
+            if u == (0, 0) || cross(rest, u, step)? != 0 {
+                return Ok(None);
+            }
+            match u.0 {
+                0 => (vec![(i, rest.1)], u.1),
+                _ => (vec![(i, rest.0)], u.0),
+            }
+        }
+        _ => match rest {
+            (0, 0) => (vec![], 1),
+            _ => return Ok(None),
+        },
+    };
+    if denominator < 0 {
+        denominator = checked(denominator.checked_neg(), step)?;
+        for (_, numerator) in &mut basic {
+            *numerator = checked(numerator.checked_neg(), step)?;
+        }
+    }
+
+    let mut numerators = values
+        .iter()
+        .map(|value| checked(value.checked_mul(denominator), step))
+        .collect::<Result<Vec<_>, _>>()?;
+    for (i, numerator) in basic {
+        let low = checked(branch.low[i].checked_mul(denominator), step)?;
+        let high = branch.high[i]
+            .map(|high| checked(high.checked_mul(denominator), step))
+            .transpose()?;
+        if numerator < low || high.is_some_and(|high| numerator > high) {
+            return Ok(None);
+        }
+        numerators[i] = numerator;
+    }
+
+    let cost = buttons
+        .iter()
+        .zip(&numerators)
+        .try_fold(0i128, |total, (button, &numerator)| {
+            let cost = numerator
+                .checked_mul(button.cost)
+                .and_then(|cost| total.checked_add(cost));
+            checked(cost, "relaxed cost")
+        })?;
+    Ok(Some(Relaxed {
+        numerators,
+        denominator,
+        cost,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{
+        tests::{button, cheapest},
+        Machine,
+    };
+
+    /// Small linear congruential generator, enough to spread the test machines around.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn below(&mut self, n: i128) -> i128 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) as i128 % n
+        }
+    }
+
+    fn random_machine(
+        rng: &mut Lcg,
+        buttons: usize,
+        low: i128,
+        high: i128,
+        prize: i128,
+    ) -> Machine {
+        let labels = ['A', 'B', 'C', 'D'];
+        Machine {
+            buttons: labels[..buttons]
+                .iter()
+                .map(|&label| {
+                    let mut button = button(
+                        label,
+                        (
+                            low + rng.below(high - low + 1),
+                            low + rng.below(high - low + 1),
+                        ),
+                    );
+                    button.cost = 1 + rng.below(4);
+                    button
+                })
+                .collect(),
+            prize: (rng.below(prize + 1), rng.below(prize + 1)),
+        }
+    }
+
+    fn minimise_machine(machine: &Machine, limit: Option<i128>) -> Option<Solution> {
+        minimise(&machine.buttons, machine.prize, limit).unwrap()
+    }
+
+    fn assert_reaches_prize(machine: &Machine, solution: &Solution, limit: Option<i128>) {
+        let mut reached = (0, 0);
+        for (button, &presses) in machine.buttons.iter().zip(&solution.presses) {
+            assert!(presses >= 0 && limit.is_none_or(|limit| presses <= limit));
+            reached.0 += presses * button.delta.0;
+            reached.1 += presses * button.delta.1;
+        }
+        assert_eq!(machine.prize, reached, "{machine}");
+        assert_eq!(cost(&machine.buttons, &solution.presses), Ok(solution.cost));
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        let mut rng = Lcg(48);
+        for buttons in 1..=4 {
+            for _ in 0..150 {
+                let machine = random_machine(&mut rng, buttons, -2, 5, 12);
+                let solution = minimise_machine(&machine, Some(5));
+                assert_eq!(
+                    cheapest(&machine, 5),
+                    solution.as_ref().map(|s| s.cost),
+                    "{machine}"
+                );
+                if let Some(solution) = solution {
+                    assert_reaches_prize(&machine, &solution, Some(5));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unbounded_against_brute_force() {
+        // with buttons that only move forwards, no button is pressed more than the prize is far
+        let mut rng = Lcg(49);
+        for _ in 0..100 {
+            let machine = random_machine(&mut rng, 3, 1, 6, 30);
+            let solution = minimise_machine(&machine, None);
+            assert_eq!(
+                cheapest(&machine, 30),
+                solution.as_ref().map(|s| s.cost),
+                "{machine}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_matches_two_button_fast_path() {
+        let mut rng = Lcg(50);
+        for _ in 0..500 {
+            let machine = random_machine(&mut rng, 2, -3, 9, 400);
+            for limit in [Some(100), None] {
+                assert_eq!(
+                    machine.solve(limit).unwrap().map(|s| s.cost),
+                    minimise_machine(&machine, limit).map(|s| s.cost),
+                    "{machine}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_far_prize() {
+        let machine = Machine {
+            buttons: vec![
+                button('A', (3, 1)),
+                button('B', (1, 3)),
+                Button {
+                    label: 'C',
+                    delta: (2, 2),
+                    cost: 1,
+                },
+            ],
+            prize: (10_000_000_000_007, 10_000_000_000_005),
+        };
+        let solution = machine.solve(None).unwrap().unwrap();
+        assert_reaches_prize(&machine, &solution, None);
+        // C is the cheapest way forward, and a single A press makes up the slant
+        assert_eq!(vec![1, 0, 5_000_000_000_002], solution.presses);
+    }
+}
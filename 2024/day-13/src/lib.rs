@@ -1,2 +1,6 @@
+pub mod ilp;
+pub mod machine;
+pub mod parser;
 pub mod part1;
 pub mod part2;
+pub mod report;
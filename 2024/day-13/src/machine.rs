@@ -0,0 +1,587 @@
+use std::fmt;
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::ilp;
+
+pub type Vector = (i128, i128);
+pub(crate) type Vector3 = (i128, i128, i128);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Button {
+    pub label: char,
+    pub delta: Vector,
+    /// Tokens per press; never negative.
+    pub cost: i128,
+}
+
+impl Button {
+    /// What a press costs when the machine does not say: 3 tokens for A and 1 for B.
+    pub fn default_cost(label: char) -> Option<i128> {
+        match label {
+            'A' => Some(3),
+            'B' => Some(1),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Machine {
+    pub buttons: Vec<Button>,
+    pub prize: Vector,
+}
+
+/// How often each button is pressed, in the order of [`Machine::buttons`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub presses: Vec<i128>,
+    pub cost: i128,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum SolveError {
+    /// Names the step of solving a machine that left the range of `i128`.
+    #[error("{0} overflows 128-bit integers")]
+    Overflow(&'static str),
+
+    #[error("no cheapest presses found within {0} branches")]
+    Exhausted(usize),
+}
+
+/// A machine that could not be solved, by its index in the input.
+#[derive(Debug, Error, Diagnostic)]
+#[error("machine {index}: {error}")]
+pub struct MachineError {
+    pub index: usize,
+    pub error: SolveError,
+    #[help]
+    machine: String,
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("{} machine(s) could not be solved", machines.len())]
+#[diagnostic(help("the total would be wrong without them, so none is given"))]
+pub struct MachineErrors {
+    #[related]
+    pub machines: Vec<MachineError>,
+}
+
+pub(crate) fn checked(value: Option<i128>, step: &'static str) -> Result<i128, SolveError> {
+    value.ok_or(SolveError::Overflow(step))
+}
+
+/// `u.0 * v.1 - u.1 * v.0`, zero exactly when `u` and `v` are collinear.
+pub(crate) fn cross(u: Vector, v: Vector, step: &'static str) -> Result<i128, SolveError> {
+    let left = checked(u.0.checked_mul(v.1), step)?;
+    let right = checked(u.1.checked_mul(v.0), step)?;
+    checked(left.checked_sub(right), step)
+}
+
+/// Tokens for pressing each of `buttons` as often as `presses` says.
+pub(crate) fn cost(buttons: &[Button], presses: &[i128]) -> Result<i128, SolveError> {
+    buttons
+        .iter()
+        .zip(presses)
+        .try_fold(0i128, |total, (button, &presses)| {
+            let cost = presses
+                .checked_mul(button.cost)
+                .and_then(|cost| total.checked_add(cost));
+            checked(cost, "cost")
+        })
+}
+
+/// The machine as it is written in the input, on one line.
+impl fmt::Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for button in &self.buttons {
+            let (x, y) = button.delta;
+            write!(f, "Button {}: X{x:+}, Y{y:+}", button.label)?;
+            if Button::default_cost(button.label) != Some(button.cost) {
+                write!(f, ", Cost={}", button.cost)?;
+            }
+            write!(f, "; ")?;
+        }
+        write!(f, "Prize: X={}, Y={}", self.prize.0, self.prize.1)
+    }
+}
+
+impl Machine {
+    /// The machine with its prize moved by `offset` along both axes.
+    pub fn offset(&self, offset: i128) -> Result<Self, SolveError> {
+        let prize = (
+            checked(self.prize.0.checked_add(offset), "prize offset")?,
+            checked(self.prize.1.checked_add(offset), "prize offset")?,
+        );
+        Ok(Self {
+            buttons: self.buttons.clone(),
+            prize,
+        })
+    }
+
+    /// The cheapest presses that reach the prize, pressing each button at most `limit` times.
+    pub fn solve(&self, limit: Option<i128>) -> Result<Option<Solution>, SolveError> {
+        let presses = match self.buttons[..] {
+            [a, b] => solve_pair(a, b, self.prize, limit)?.map(|(a, b)| vec![a, b]),
+            _ => return ilp::minimise(&self.buttons, self.prize, limit),
+        };
+        presses
+            .map(|presses| {
+                let cost = cost(&self.buttons, &presses)?;
+                Ok(Solution { presses, cost })
+            })
+            .transpose()
+    }
+}
+
+/// Cramer's rule: independent buttons reach the prize in exactly one way, pressing them
+/// `da / determinant` and `db / determinant` times. `None` for collinear buttons.
+pub(crate) fn cramer(a: Vector, b: Vector, c: Vector) -> Result<Option<Vector3>, SolveError> {
+    let determinant = cross(a, b, "determinant")?;
+    if determinant == 0 {
+        return Ok(None);
+    }
+    let da = cross(c, b, "presses of A")?;
+    let db = cross(a, c, "presses of B")?;
+    Ok(Some((da, db, determinant)))
+}
+
+/// The fast path for two buttons, as in the puzzle.
+fn solve_pair(
+    a: Button,
+    b: Button,
+    c: Vector,
+    limit: Option<i128>,
+) -> Result<Option<(i128, i128)>, SolveError> {
+    let Some((da, db, determinant)) = cramer(a.delta, b.delta, c)? else {
+        return solve_collinear(a, b, c, limit);
+    };
+    // `checked_rem` only fails where the quotient would overflow too, like `i128::MIN / -1`
+    if checked(da.checked_rem(determinant), "presses of A")? != 0
+        || checked(db.checked_rem(determinant), "presses of B")? != 0
+    {
+        return Ok(None);
+    }
+    let presses = (
+        checked(da.checked_div(determinant), "presses of A")?,
+        checked(db.checked_div(determinant), "presses of B")?,
+    );
+    Ok((within(presses.0, limit) && within(presses.1, limit)).then_some(presses))
+}
+
+/// Buttons that move along the same line reach every point of it in many ways, if any: the
+/// presses solve `a * α + b * β = γ` along one axis, and the cheapest solution lies at one end
+/// of the range the press limits allow.
+fn solve_collinear(
+    a: Button,
+    b: Button,
+    c: Vector,
+    limit: Option<i128>,
+) -> Result<Option<(i128, i128)>, SolveError> {
+    if a.delta == (0, 0) && b.delta == (0, 0) {
+        return Ok((c == (0, 0)).then_some((0, 0)));
+    }
+    let Some((alpha, beta, gamma)) = collinear_axis(a.delta, b.delta, c)? else {
+        return Ok(None);
+    };
+    let single = |step: i128| -> Result<Option<i128>, SolveError> {
+        let presses = "presses of a single button";
+        Ok((checked(gamma.checked_rem(step), presses)? == 0)
+            .then_some(checked(gamma.checked_div(step), presses)?)
+            .filter(|&presses| within(presses, limit)))
+    };
+    let (g, x, y) = match (alpha, beta) {
+        // a button that does not move is never worth pressing
+        (0, _) => return Ok(single(beta)?.map(|b| (0, b))),
+        (_, 0) => return Ok(single(alpha)?.map(|a| (a, 0))),
+        _ => extended_gcd(alpha, beta)?,
+    };
+    if gamma % g != 0 {
+        return Ok(None);
+    }
+
+    // a = a0 + t * p, b = b0 - t * q for every integer t
+    let step = "collinear presses";
+    let a0 = checked(x.checked_mul(gamma / g), step)?;
+    let b0 = checked(y.checked_mul(gamma / g), step)?;
+    let (p, q) = (beta / g, alpha / g);
+
+    let (low_a, high_a) = steps_within(a0, p, limit)?;
+    let (low_b, high_b) = steps_within(b0, checked(q.checked_neg(), step)?, limit)?;
+    // `None` orders before every bound, so the larger lower bound is the tighter one
+    let low = low_a.max(low_b);
+    let high = min_bound(high_a, high_b);
+    if let (Some(low), Some(high)) = (low, high) {
+        if low > high {
+            return Ok(None);
+        }
+    }
+
+    // the cost changes by `slope` per step of t; on ties press A as little as possible
+    let slope = checked(
+        p.checked_mul(a.cost)
+            .zip(q.checked_mul(b.cost))
+            .and_then(|(p, q)| p.checked_sub(q)),
+        "cost slope",
+    )?;
+    let t = match (slope, p) {
+        (slope, _) if slope < 0 => high,
+        (slope, _) if slope > 0 => low,
+        (_, p) if p > 0 => low,
+        _ => high,
+    };
+    // costs are not negative, so the cheaper end of the range is always bounded
+    let t = t.expect("cheaper end of the collinear range is bounded");
+    Ok(Some((
+        checked(t.checked_mul(p).and_then(|tp| a0.checked_add(tp)), step)?,
+        checked(t.checked_mul(q).and_then(|tq| b0.checked_sub(tq)), step)?,
+    )))
+}
+
+/// Collinear buttons `a` and `b` reach `c` when `alpha * a + beta * b = gamma`, along the axis
+/// their line moves along. `None` if `c` is off that line or neither button moves.
+pub(crate) fn collinear_axis(
+    a: Vector,
+    b: Vector,
+    c: Vector,
+) -> Result<Option<Vector3>, SolveError> {
+    let Some(direction) = [a, b].into_iter().find(|&v| v != (0, 0)) else {
+        return Ok(None);
+    };
+    if cross(c, direction, "prize direction")? != 0 {
+        return Ok(None);
+    }
+
+    // every point of the line is told apart by the axis the direction moves along
+    Ok(Some(match direction.0 {
+        0 => (a.1, b.1, c.1),
+        _ => (a.0, b.0, c.0),
+    }))
+}
+
+fn within(presses: i128, limit: Option<i128>) -> bool {
+    0 <= presses && limit.is_none_or(|limit| presses <= limit)
+}
+
+/// `(g, x, y)` with `a * x + b * y = g`, where `g` is the greatest common divisor of `a` and `b`
+/// and positive.
+fn extended_gcd(a: i128, b: i128) -> Result<(i128, i128, i128), SolveError> {
+    let step = "greatest common divisor";
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1i128, 0i128);
+    let (mut old_y, mut y) = (0i128, 1i128);
+    while r != 0 {
+        let quotient = checked(old_r.checked_div(r), step)?;
+        let next = |old: i128, current: i128| {
+            checked(
+                quotient
+                    .checked_mul(current)
+                    .and_then(|product| old.checked_sub(product)),
+                step,
+            )
+        };
+        (old_r, r) = (r, next(old_r, r)?);
+        (old_x, x) = (x, next(old_x, x)?);
+        (old_y, y) = (y, next(old_y, y)?);
+    }
+    match old_r < 0 {
+        true => Ok((
+            checked(old_r.checked_neg(), step)?,
+            checked(old_x.checked_neg(), step)?,
+            checked(old_y.checked_neg(), step)?,
+        )),
+        false => Ok((old_r, old_x, old_y)),
+    }
+}
+
+/// The range of integers `t` for which `0 <= start + t * step <= limit`, open where unbounded.
+/// `step` is not zero.
+fn steps_within(
+    start: i128,
+    step: i128,
+    limit: Option<i128>,
+) -> Result<(Option<i128>, Option<i128>), SolveError> {
+    let name = "press limits";
+    let low = checked(start.checked_neg(), name)?;
+    let high = limit
+        .map(|limit| checked(limit.checked_sub(start), name))
+        .transpose()?;
+    let floor = |value: i128| checked(div_floor(value, step), name);
+    let ceil = |value: i128| checked(div_ceil(value, step), name);
+    // `low <= t * step <= high`, so dividing by a negative step swaps the bounds
+    match step > 0 {
+        true => Ok((Some(ceil(low)?), high.map(floor).transpose()?)),
+        false => Ok((high.map(ceil).transpose()?, Some(floor(low)?))),
+    }
+}
+
+fn min_bound(a: Option<i128>, b: Option<i128>) -> Option<i128> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+pub(crate) fn div_floor(a: i128, b: i128) -> Option<i128> {
+    let quotient = a.checked_div(b)?;
+    match (a.checked_rem(b)? != 0) && ((a < 0) != (b < 0)) {
+        true => Some(quotient - 1),
+        false => Some(quotient),
+    }
+}
+
+pub(crate) fn div_ceil(a: i128, b: i128) -> Option<i128> {
+    let quotient = a.checked_div(b)?;
+    match (a.checked_rem(b)? != 0) && ((a < 0) == (b < 0)) {
+        true => Some(quotient + 1),
+        false => Some(quotient),
+    }
+}
+
+/// Tokens needed to win every prize that can be won, with every prize moved by `offset` and
+/// each button pressed at most `limit` times. Machines that could not be solved, like those
+/// whose arithmetic would overflow, are all reported by index instead of a total.
+pub fn total_cost(
+    machines: &[Machine],
+    offset: i128,
+    limit: Option<i128>,
+) -> Result<i128, MachineErrors> {
+    let mut total = Some(0i128);
+    let mut errors = Vec::new();
+    for (index, machine) in machines.iter().enumerate() {
+        let cost = machine
+            .offset(offset)
+            .and_then(|machine| machine.solve(limit))
+            .map(|solution| solution.map(|solution| solution.cost));
+        let sum = cost.and_then(|cost| match (total, cost) {
+            (Some(sum), Some(cost)) => checked(sum.checked_add(cost), "total cost").map(Some),
+            (sum, _) => Ok(sum),
+        });
+        match sum {
+            Ok(sum) => total = sum,
+            Err(error) => {
+                if error == SolveError::Overflow("total cost") {
+                    total = None;
+                }
+                errors.push(MachineError {
+                    index,
+                    error,
+                    machine: machine.to_string(),
+                });
+            }
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(total.unwrap_or_default()),
+        false => Err(MachineErrors { machines: errors }),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    pub(crate) fn button(label: char, delta: Vector) -> Button {
+        Button {
+            label,
+            delta,
+            cost: Button::default_cost(label).unwrap_or(1),
+        }
+    }
+
+    pub(crate) fn machine(a: Vector, b: Vector, prize: Vector) -> Machine {
+        Machine {
+            buttons: vec![button('A', a), button('B', b)],
+            prize,
+        }
+    }
+
+    fn solve(machine: &Machine, limit: Option<i128>) -> Option<(i128, i128)> {
+        let solution = machine.solve(limit).unwrap()?;
+        Some((solution.presses[0], solution.presses[1]))
+    }
+
+    /// Every combination of presses up to `limit`, for checking the cheapest solution by brute
+    /// force.
+    pub(crate) fn cheapest(machine: &Machine, limit: i128) -> Option<i128> {
+        let mut cheapest = None;
+        let mut presses = vec![0; machine.buttons.len()];
+        loop {
+            let reached =
+                machine
+                    .buttons
+                    .iter()
+                    .zip(&presses)
+                    .fold((0, 0), |(x, y), (button, &presses)| {
+                        (x + presses * button.delta.0, y + presses * button.delta.1)
+                    });
+            if reached == machine.prize {
+                let cost = cost(&machine.buttons, &presses).unwrap();
+                cheapest = Some(cheapest.map_or(cost, |cheapest: i128| cheapest.min(cost)));
+            }
+            let Some(i) = presses.iter().position(|&presses| presses < limit) else {
+                return cheapest;
+            };
+            presses[..i].fill(0);
+            presses[i] += 1;
+        }
+    }
+
+    #[rstest]
+    #[case(machine((94, 34), (22, 67), (8400, 5400)), Some((80, 40)))]
+    #[case(machine((26, 66), (67, 21), (12748, 12176)), None)]
+    #[case(machine((17, 86), (84, 37), (7870, 6450)), Some((38, 86)))]
+    #[case(machine((69, 23), (27, 71), (18641, 10279)), None)]
+    fn test_independent(#[case] machine: Machine, #[case] expected: Option<(i128, i128)>) {
+        assert_eq!(expected, solve(&machine, Some(100)));
+    }
+
+    #[rstest]
+    // B is cheaper per unit of distance, so as many B presses as fit
+    #[case(machine((6, 4), (3, 2), (30, 20)), Some(100), Some((0, 10)))]
+    // A covers more than three times the distance of B, so it is the cheaper one
+    #[case(machine((8, 4), (2, 1), (20, 10)), Some(100), Some((2, 2)))]
+    // not a multiple of the step both buttons share
+    #[case(machine((4, 0), (6, 0), (9, 0)), Some(100), None)]
+    #[case(machine((4, 2), (6, 3), (10, 5)), Some(100), Some((1, 1)))]
+    // the prize is on a different line
+    #[case(machine((1, 1), (2, 2), (5, 6)), Some(100), None)]
+    // only reachable with more presses than the limit allows
+    #[case(machine((1, 0), (1, 0), (250, 0)), Some(100), None)]
+    #[case(machine((1, 0), (1, 0), (250, 0)), None, Some((0, 250)))]
+    // buttons moving in opposite directions
+    #[case(machine((5, 0), (-3, 0), (1, 0)), Some(100), Some((2, 3)))]
+    // vertical buttons
+    #[case(machine((0, 7), (0, 3), (0, 20)), Some(100), Some((2, 2)))]
+    // one press of A is worth exactly three of B: fewest A presses
+    #[case(machine((3, 3), (1, 1), (9, 9)), Some(100), Some((0, 9)))]
+    #[case(machine((3, 3), (1, 1), (300, 300)), Some(100), Some((67, 99)))]
+    fn test_collinear(
+        #[case] machine: Machine,
+        #[case] limit: Option<i128>,
+        #[case] expected: Option<(i128, i128)>,
+    ) {
+        assert_eq!(expected, solve(&machine, limit));
+    }
+
+    #[rstest]
+    #[case(machine((0, 0), (0, 0), (0, 0)), Some((0, 0)))]
+    #[case(machine((0, 0), (0, 0), (1, 0)), None)]
+    #[case(machine((0, 0), (2, 3), (8, 12)), Some((0, 4)))]
+    #[case(machine((2, 3), (0, 0), (8, 12)), Some((4, 0)))]
+    #[case(machine((2, 3), (0, 0), (7, 12)), None)]
+    #[case(machine((0, 0), (2, 3), (9, 12)), None)]
+    fn test_zero_buttons(#[case] machine: Machine, #[case] expected: Option<(i128, i128)>) {
+        assert_eq!(expected, solve(&machine, Some(100)));
+    }
+
+    #[test]
+    fn test_collinear_against_brute_force() {
+        let steps = [-4, -3, -1, 0, 1, 2, 3, 5, 6, 9];
+        for &(ka, kb) in &[(1, 1), (2, 3), (-1, 2), (3, 1), (0, 1)] {
+            for &step in &steps {
+                for &other in &steps {
+                    for target in -12..=40 {
+                        let m = machine((ka * step, 0), (kb * other, 0), (target, 0));
+                        let solved = m.solve(Some(15)).unwrap();
+                        assert_eq!(cheapest(&m, 15), solved.as_ref().map(|s| s.cost), "{m:?}");
+                        if let Some(solved) = solved {
+                            let [a, b] = solved.presses[..] else {
+                                unreachable!()
+                            };
+                            assert!((0..=15).contains(&a) && (0..=15).contains(&b));
+                            assert_eq!(target, a * m.buttons[0].delta.0 + b * m.buttons[1].delta.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_costs() {
+        let mut m = machine((6, 4), (3, 2), (30, 20));
+        m.buttons[1].cost = 5;
+        assert_eq!(Some((5, 0)), solve(&m, Some(100)));
+    }
+
+    #[rstest]
+    #[case(machine((i128::MAX, 1), (1, i128::MAX), (1, 1)), "determinant")]
+    #[case(machine((2, 1), (1, 2), (i128::MAX, i128::MAX)), "presses of A")]
+    #[case(machine((2, 2), (4, 4), (i128::MAX, i128::MAX)), "prize direction")]
+    fn test_overflow(#[case] machine: Machine, #[case] step: &'static str) {
+        assert_eq!(Err(SolveError::Overflow(step)), machine.solve(None));
+    }
+
+    #[test]
+    fn test_total_cost_reports_overflowing_machines() {
+        let machines = [
+            machine((94, 34), (22, 67), (8400, 5400)),
+            machine((i128::MAX, 1), (1, i128::MAX), (1, 1)),
+            machine((1, 0), (0, 1), (i128::MAX - 5, 0)),
+            machine((1, 0), (0, 1), (i128::MAX / 2, 0)),
+        ];
+        assert_eq!(280, total_cost(&machines[..1], 0, Some(100)).unwrap());
+
+        let errors = total_cost(&machines, 10, None).unwrap_err().machines;
+        let reported = errors
+            .iter()
+            .map(|error| (error.index, error.error))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (1, SolveError::Overflow("determinant")),
+                (2, SolveError::Overflow("prize offset")),
+                (3, SolveError::Overflow("cost")),
+            ],
+            reported
+        );
+        assert_eq!(
+            "Button A: X+1, Y+0; Button B: X+0, Y+1; Prize: X=85070591730234615865843651857942052863, Y=0",
+            errors[2].machine
+        );
+    }
+
+    #[test]
+    fn test_total_overflow() {
+        let big = i128::MAX / 3 - 1;
+        let machines = [
+            machine((1, 0), (0, 1), (big, 0)),
+            machine((1, 0), (0, 1), (big, 0)),
+        ];
+        let errors = total_cost(&machines, 0, None).unwrap_err().machines;
+        assert_eq!(1, errors.len());
+        assert_eq!(
+            (1, SolveError::Overflow("total cost")),
+            (errors[0].index, errors[0].error)
+        );
+    }
+
+    #[rstest]
+    #[case(7, 2, 3, 4)]
+    #[case(-7, 2, -4, -3)]
+    #[case(7, -2, -4, -3)]
+    #[case(-7, -2, 3, 4)]
+    #[case(6, 3, 2, 2)]
+    #[case(-6, 3, -2, -2)]
+    fn test_division(#[case] a: i128, #[case] b: i128, #[case] floor: i128, #[case] ceil: i128) {
+        assert_eq!((Some(floor), Some(ceil)), (div_floor(a, b), div_ceil(a, b)));
+    }
+
+    #[rstest]
+    #[case(240, 46)]
+    #[case(-4, 6)]
+    #[case(3, -9)]
+    #[case(0, 5)]
+    fn test_extended_gcd(#[case] a: i128, #[case] b: i128) {
+        let (g, x, y) = extended_gcd(a, b).unwrap();
+        assert!(g > 0);
+        assert_eq!(0, a % g);
+        assert_eq!(0, b % g);
+        assert_eq!(g, a * x + b * y);
+    }
+}
@@ -0,0 +1,272 @@
+use miette::{Diagnostic, SourceSpan};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{self, satisfy, space0},
+    combinator::{all_consuming, map, opt},
+    sequence::{preceded, separated_pair, terminated, tuple},
+    IResult,
+};
+use thiserror::Error;
+
+use crate::machine::{Button, Machine, Vector};
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum ParseError {
+    #[error("no machines in the input")]
+    Empty,
+
+    #[error("invalid line {line}")]
+    #[diagnostic(help(
+        "machines are `Button <label>: X+<x>, Y+<y>` lines, optionally ending in `, Cost=<tokens>`, \
+         then a `Prize: X=<x>, Y=<y>` line"
+    ))]
+    InvalidLine {
+        line: usize,
+        #[source_code]
+        src: String,
+        #[label("expected a button or a prize")]
+        span: SourceSpan,
+    },
+
+    #[error("button {label} on line {line} has no cost")]
+    #[diagnostic(help("only A and B have default costs; add `, Cost=<tokens>` to the others"))]
+    MissingCost {
+        label: char,
+        line: usize,
+        #[source_code]
+        src: String,
+        #[label("expected `, Cost=<tokens>`")]
+        span: SourceSpan,
+    },
+
+    #[error("machine on line {line} has no prize")]
+    MissingPrize {
+        line: usize,
+        #[source_code]
+        src: String,
+        #[label("expected a `Prize: X=<x>, Y=<y>` line after these buttons")]
+        span: SourceSpan,
+    },
+
+    #[error("prize on line {line} has no buttons")]
+    MissingButtons {
+        line: usize,
+        #[source_code]
+        src: String,
+        #[label("expected `Button <label>: X+<x>, Y+<y>` lines before the prize")]
+        span: SourceSpan,
+    },
+}
+
+enum Line {
+    Button {
+        label: char,
+        delta: Vector,
+        cost: Option<i128>,
+    },
+    Prize(Vector),
+}
+
+/// `X<x>, Y<y>` after a button, where the offsets are signed like `+94` or `-5`.
+fn delta(input: &str) -> IResult<&str, Vector> {
+    separated_pair(
+        preceded(tag("X"), complete::i128),
+        tag(", "),
+        preceded(tag("Y"), complete::i128),
+    )(input)
+}
+
+/// `Button <label>: X+<x>, Y+<y>`, optionally with `, Cost=<tokens>`.
+fn button(input: &str) -> IResult<&str, Line> {
+    map(
+        tuple((
+            preceded(tag("Button "), satisfy(|c| c.is_ascii_alphanumeric())),
+            preceded(tag(": "), delta),
+            opt(preceded(tag(", Cost="), complete::u64)),
+        )),
+        |(label, delta, cost)| Line::Button {
+            label,
+            delta,
+            cost: cost.map(i128::from),
+        },
+    )(input)
+}
+
+fn prize(input: &str) -> IResult<&str, Line> {
+    map(
+        separated_pair(
+            preceded(tag("Prize: X="), complete::i128),
+            tag(", "),
+            preceded(tag("Y="), complete::i128),
+        ),
+        Line::Prize,
+    )(input)
+}
+
+/// Machines separated by blank lines, each some buttons and then its prize. Lines may end in
+/// `\n` or `\r\n`, and the last one in neither.
+pub fn parse(input: &str) -> Result<Vec<Machine>, ParseError> {
+    let span = |line: &str| {
+        SourceSpan::from((line.as_ptr() as usize - input.as_ptr() as usize, line.len()))
+    };
+
+    let mut machines = Vec::new();
+    let mut buttons = Vec::new();
+    // the first line and the span of the buttons of the machine being read
+    let mut first: Option<(usize, SourceSpan)> = None;
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            if let Some((line, span)) = first {
+                return Err(ParseError::MissingPrize {
+                    line,
+                    src: input.to_string(),
+                    span,
+                });
+            }
+            continue;
+        }
+
+        let (_, parsed) =
+            all_consuming(terminated(alt((button, prize)), space0))(line).map_err(|_| {
+                ParseError::InvalidLine {
+                    line: i + 1,
+                    src: input.to_string(),
+                    span: span(line),
+                }
+            })?;
+        match parsed {
+            Line::Button { label, delta, cost } => {
+                let cost = cost.or(Button::default_cost(label)).ok_or_else(|| {
+                    ParseError::MissingCost {
+                        label,
+                        line: i + 1,
+                        src: input.to_string(),
+                        span: (span(line).offset() + line.len(), 0).into(),
+                    }
+                })?;
+                buttons.push(Button { label, delta, cost });
+                first = Some(match first {
+                    Some((first, start)) => {
+                        let end = span(line).offset() + line.len();
+                        (first, (start.offset(), end - start.offset()).into())
+                    }
+                    None => (i + 1, span(line)),
+                });
+            }
+            Line::Prize(prize) => {
+                if buttons.is_empty() {
+                    return Err(ParseError::MissingButtons {
+                        line: i + 1,
+                        src: input.to_string(),
+                        span: span(line),
+                    });
+                }
+                machines.push(Machine {
+                    buttons: std::mem::take(&mut buttons),
+                    prize,
+                });
+                first = None;
+            }
+        }
+    }
+
+    if let Some((line, span)) = first {
+        return Err(ParseError::MissingPrize {
+            line,
+            src: input.to_string(),
+            span,
+        });
+    }
+    match machines.is_empty() {
+        true => Err(ParseError::Empty),
+        false => Ok(machines),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::machine::tests::machine;
+
+    #[test]
+    fn test_example() {
+        let input = "Button A: X+94, Y+34
+Button B: X+22, Y+67
+Prize: X=8400, Y=5400
+
+Button A: X+26, Y+66
+Button B: X+67, Y+21
+Prize: X=12748, Y=12176
+";
+        assert_eq!(
+            vec![
+                machine((94, 34), (22, 67), (8400, 5400)),
+                machine((26, 66), (67, 21), (12748, 12176)),
+            ],
+            parse(input).unwrap()
+        );
+    }
+
+    #[rstest]
+    #[case::no_final_newline("Button A: X+1, Y+2\nButton B: X+3, Y+4\nPrize: X=5, Y=6")]
+    #[case::crlf("Button A: X+1, Y+2\r\nButton B: X+3, Y+4\r\nPrize: X=5, Y=6\r\n")]
+    #[case::blank_lines("\n\nButton A: X+1, Y+2\nButton B: X+3, Y+4\nPrize: X=5, Y=6\n\n\n")]
+    #[case::trailing_spaces("Button A: X+1, Y+2  \nButton B: X+3, Y+4\nPrize: X=5, Y=6 \n")]
+    fn test_tolerated(#[case] input: &str) {
+        assert_eq!(vec![machine((1, 2), (3, 4), (5, 6))], parse(input).unwrap());
+    }
+
+    #[test]
+    fn test_negative_deltas_and_costs() {
+        let input = "Button A: X-5, Y+3
+Button B: X+0, Y-2
+
+
+Button A: X+1, Y+1
+Button B: X+2, Y+2
+Button C: X+3, Y-3, Cost=7
+Prize: X=-10, Y=-4\r\n";
+        let error = parse(input).unwrap_err();
+        assert!(matches!(error, ParseError::MissingPrize { line: 1, .. }));
+
+        let input = input.replacen("\n\n\n", "\nPrize: X=-5, Y=1\n\n", 1);
+        let machines = parse(&input).unwrap();
+        assert_eq!(machine((-5, 3), (0, -2), (-5, 1)), machines[0]);
+        assert_eq!((3, -3), machines[1].buttons[2].delta);
+        assert_eq!(7, machines[1].buttons[2].cost);
+        assert_eq!((-10, -4), machines[1].prize);
+    }
+
+    fn offsets(error: &ParseError) -> Option<(usize, usize, usize)> {
+        match error {
+            ParseError::Empty => None,
+            ParseError::InvalidLine { line, span, .. }
+            | ParseError::MissingCost { line, span, .. }
+            | ParseError::MissingPrize { line, span, .. }
+            | ParseError::MissingButtons { line, span, .. } => {
+                Some((*line, span.offset(), span.len()))
+            }
+        }
+    }
+
+    #[rstest]
+    #[case("", None)]
+    #[case("\n\r\n", None)]
+    // the `Prize` line of the second machine is misspelled
+    #[case(
+        "Button A: X+1, Y+2\nPrize: X=5, Y=6\n\nButton A: X+1, Y+2\nPrise: X=5, Y=6\n",
+        Some((5, 55, 15))
+    )]
+    #[case("Button A: X+1, Y+2\r\nButton B: X+3\r\nPrize: X=5, Y=6", Some((2, 20, 13)))]
+    #[case("Button A: X+1, Y+2, Cost=-1\nPrize: X=5, Y=6", Some((1, 0, 27)))]
+    #[case("Button A: X+1, Y+2\nButton C: X+3, Y+4\nPrize: X=5, Y=6", Some((2, 37, 0)))]
+    #[case("Button A: X+1, Y+2\nButton B: X+3, Y+4\n\nPrize: X=5, Y=6", Some((1, 0, 37)))]
+    #[case("Button A: X+1, Y+2\nButton B: X+3, Y+4\n", Some((1, 0, 37)))]
+    #[case("Prize: X=5, Y=6\n", Some((1, 0, 15)))]
+    fn test_errors(#[case] input: &str, #[case] expected: Option<(usize, usize, usize)>) {
+        assert_eq!(expected, offsets(&parse(input).unwrap_err()));
+    }
+}
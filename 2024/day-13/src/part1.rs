@@ -1,60 +1,25 @@
-use miette::miette;
-use nom::{
-    bytes::complete::tag,
-    character::complete::{self, line_ending},
-    multi::separated_list1,
-    sequence::{delimited, preceded, tuple},
-    IResult,
+use crate::{
+    machine,
+    parser::parse,
+    report::{self, MachineOutcome},
 };
 
-fn parse(input: &str) -> IResult<&str, Vec<((i64, i64), (i64, i64), (i64, i64))>> {
-    separated_list1(
-        line_ending,
-        tuple((
-            tuple((
-                preceded(tag("Button A: X+"), complete::i64),
-                delimited(tag(", Y+"), complete::i64, line_ending),
-            )),
-            tuple((
-                preceded(tag("Button B: X+"), complete::i64),
-                delimited(tag(", Y+"), complete::i64, line_ending),
-            )),
-            tuple((
-                preceded(tag("Prize: X="), complete::i64),
-                delimited(tag(", Y="), complete::i64, line_ending),
-            )),
-        )),
-    )(input)
-}
-
 #[tracing::instrument]
-pub fn process(input: &str) -> miette::Result<i64> {
-    let (_, input) = parse(input).map_err(|e| miette!("parse failed {}", e))?;
+pub fn process(input: &str) -> miette::Result<i128> {
+    let machines = parse(input)?;
 
-    let total = input
-        .into_iter()
-        .map(|(a, b, c)| {
-            let determinant = a.0 * b.1 - a.1 * b.0;
-            ((a, b, c), determinant)
-        })
-        .filter(|(_, determinant)| *determinant != 0)
-        .map(|((a, b, c), d)| {
-            let dx = c.0 * b.1 - c.1 * b.0;
-            let dy = a.0 * c.1 - a.1 * c.0;
-            (dx, dy, d)
-        })
-        .filter_map(|(dx, dy, d)| match (dx % d, dy % d) {
-            (0, 0) => Some((dx / d, dy / d)),
-            _ => None,
-        })
-        .filter(|(a, b)| 0 <= *a && *a <= 100 && 0 <= *b && *b <= 100)
-        // .inspect(|a| _ = dbg!(a))
-        .map(|(a, b)| a * 3 + b)
-        .sum();
+    let total = machine::total_cost(&machines, 0, Some(100))?;
 
     Ok(total)
 }
 
+/// The outcome of every machine, for the summary table.
+pub fn report(input: &str) -> miette::Result<Vec<MachineOutcome>> {
+    let machines = parse(input)?;
+
+    Ok(report::outcomes(&machines, 0, Some(100)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -81,4 +46,24 @@ Prize: X=18641, Y=10279
         assert_eq!(480, process(input)?);
         Ok(())
     }
+
+    #[test_log::test]
+    fn test_more_buttons() -> miette::Result<()> {
+        let input = "Button A: X+3, Y+1
+Button B: X+1, Y+3
+Button C: X+2, Y+2, Cost=1
+Prize: X=20, Y=20
+";
+        assert_eq!(10, process(input)?);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_button_without_cost() {
+        let input = "Button A: X+3, Y+1
+Button C: X+2, Y+2
+Prize: X=20, Y=20
+";
+        assert!(process(input).is_err());
+    }
 }
@@ -0,0 +1,401 @@
+//! What became of each machine, not just the summed tokens: the presses and cost of the
+//! solved ones, and for the rest why they are out of reach, with the exact press counts of
+//! near misses.
+
+use std::fmt;
+
+use crate::machine::{self, checked, Button, Machine, Solution, SolveError, Vector};
+
+/// An exact press count that need not be whole, reduced and with a positive denominator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fraction {
+    pub numerator: i128,
+    pub denominator: i128,
+}
+
+impl Fraction {
+    pub fn new(numerator: i128, denominator: i128) -> Result<Self, SolveError> {
+        let step = "press fraction";
+        let g = gcd(numerator.unsigned_abs(), denominator.unsigned_abs());
+        let g = checked(i128::try_from(g).ok(), step)?;
+        let (mut numerator, mut denominator) = (
+            checked(numerator.checked_div(g), step)?,
+            checked(denominator.checked_div(g), step)?,
+        );
+        if denominator < 0 {
+            numerator = checked(numerator.checked_neg(), step)?;
+            denominator = checked(denominator.checked_neg(), step)?;
+        }
+        Ok(Self {
+            numerator,
+            denominator,
+        })
+    }
+
+    pub fn is_whole(self) -> bool {
+        self.denominator == 1
+    }
+}
+
+impl fmt::Display for Fraction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.is_whole() {
+            true => write!(f, "{}", self.numerator),
+            false => write!(f, "{}/{}", self.numerator, self.denominator),
+        }
+    }
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    match b {
+        0 => a.max(1),
+        _ => gcd(b, a % b),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Solved(Solution),
+    /// Reaching the prize takes a fractional number of presses of some button. Buttons along
+    /// the same line have many such presses; these are the cheapest.
+    NonInteger(Vec<Fraction>),
+    /// Whole presses reach the prize, but only pressing some button more than the limit allows.
+    ExceedsLimit(Vec<i128>),
+    /// Whole presses reach the prize, but only pressing some button backwards.
+    NegativePresses(Vec<i128>),
+    /// No presses reach the prize.
+    Unreachable,
+    Failed(SolveError),
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Solved(_) => "solved",
+            Self::NonInteger(_) => "non-integer",
+            Self::ExceedsLimit(_) => "exceeds limit",
+            Self::NegativePresses(_) => "negative presses",
+            Self::Unreachable => "unreachable",
+            Self::Failed(_) => "failed",
+        }
+    }
+
+    /// The outcome of `machine`, pressing each button at most `limit` times.
+    pub fn of(machine: &Machine, limit: Option<i128>) -> Result<Self, SolveError> {
+        let [a, b] = machine.buttons[..] else {
+            return Self::searched(machine, limit);
+        };
+        let Some((da, db, determinant)) = machine::cramer(a.delta, b.delta, machine.prize)? else {
+            return match collinear_miss(a, b, machine.prize)? {
+                Some(presses) => Ok(Self::NonInteger(presses)),
+                None => Self::searched(machine, limit),
+            };
+        };
+
+        // independent buttons have a single rational solution, so it shows how close it came
+        let presses = [
+            Fraction::new(da, determinant)?,
+            Fraction::new(db, determinant)?,
+        ];
+        if !presses.iter().all(|presses| presses.is_whole()) {
+            return Ok(Self::NonInteger(presses.to_vec()));
+        }
+        let presses = presses.map(|presses| presses.numerator).to_vec();
+        if presses.iter().any(|&presses| presses < 0) {
+            return Ok(Self::NegativePresses(presses));
+        }
+        if presses
+            .iter()
+            .any(|&presses| limit.is_some_and(|l| presses > l))
+        {
+            return Ok(Self::ExceedsLimit(presses));
+        }
+        let cost = machine::cost(&machine.buttons, &presses)?;
+        Ok(Self::Solved(Solution { presses, cost }))
+    }
+
+    /// The outcome of machines with more than one way to the prize, if any: solved within the
+    /// limit, or reachable only past it.
+    fn searched(machine: &Machine, limit: Option<i128>) -> Result<Self, SolveError> {
+        if let Some(solution) = machine.solve(limit)? {
+            return Ok(Self::Solved(solution));
+        }
+        let unlimited = match limit {
+            Some(_) => machine.solve(None)?,
+            None => None,
+        };
+        match unlimited {
+            Some(solution) => Ok(Self::ExceedsLimit(solution.presses)),
+            None => Ok(Self::Unreachable),
+        }
+    }
+}
+
+/// The cheapest fractional presses of collinear buttons whose line passes through the prize
+/// when no whole presses can land on it: only the button with fewer tokens per step along the
+/// line, preferring one that is not pressed backwards.
+fn collinear_miss(
+    a: Button,
+    b: Button,
+    prize: Vector,
+) -> Result<Option<Vec<Fraction>>, SolveError> {
+    let Some((alpha, beta, gamma)) = machine::collinear_axis(a.delta, b.delta, prize)? else {
+        return Ok(None);
+    };
+    if gamma.unsigned_abs() % gcd(alpha.unsigned_abs(), beta.unsigned_abs()) == 0 {
+        return Ok(None);
+    }
+
+    let step = "press fraction";
+    let forwards = |along: i128| along != 0 && (along < 0) == (gamma < 0);
+    let a_cheaper = checked(a.cost.checked_mul(beta.abs()), step)?
+        <= checked(b.cost.checked_mul(alpha.abs()), step)?;
+    let press_a = match (forwards(alpha), forwards(beta)) {
+        (true, true) => a_cheaper,
+        (true, false) => true,
+        (false, true) => false,
+        (false, false) => alpha != 0,
+    };
+    let zero = Fraction::new(0, 1)?;
+    Ok(Some(match press_a {
+        true => vec![Fraction::new(gamma, alpha)?, zero],
+        false => vec![zero, Fraction::new(gamma, beta)?],
+    }))
+}
+
+/// The outcome of one machine, with its prize moved as it was solved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineOutcome {
+    pub index: usize,
+    pub machine: Machine,
+    pub outcome: Outcome,
+}
+
+/// The outcome of every machine, in input order, with every prize moved by `offset` and each
+/// button pressed at most `limit` times.
+pub fn outcomes(machines: &[Machine], offset: i128, limit: Option<i128>) -> Vec<MachineOutcome> {
+    machines
+        .iter()
+        .enumerate()
+        .map(|(index, machine)| {
+            let (machine, outcome) = match machine.offset(offset) {
+                Ok(machine) => {
+                    let outcome = Outcome::of(&machine, limit).unwrap_or_else(Outcome::Failed);
+                    (machine, outcome)
+                }
+                Err(error) => (machine.clone(), Outcome::Failed(error)),
+            };
+            MachineOutcome {
+                index,
+                machine,
+                outcome,
+            }
+        })
+        .collect()
+}
+
+/// A table of outcomes with one machine per line, then the count of each outcome and the
+/// tokens the solved machines take.
+pub struct Summary<'a>(pub &'a [MachineOutcome]);
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let costs = self
+            .0
+            .iter()
+            .map(|outcome| match &outcome.outcome {
+                Outcome::Solved(solution) => solution.cost.to_string(),
+                _ => "-".to_string(),
+            })
+            .collect::<Vec<_>>();
+        let width = costs.iter().map(String::len).max().unwrap_or(0).max(4);
+
+        writeln!(f, "machine  {:<16}  {:>width$}  presses", "outcome", "cost")?;
+        for (outcome, cost) in self.0.iter().zip(&costs) {
+            write!(
+                f,
+                "{:>7}  {:<16}  {cost:>width$}  ",
+                outcome.index,
+                outcome.outcome.name()
+            )?;
+            let labels = outcome.machine.buttons.iter().map(|button| button.label);
+            match &outcome.outcome {
+                Outcome::Solved(Solution { presses, .. })
+                | Outcome::ExceedsLimit(presses)
+                | Outcome::NegativePresses(presses) => write_presses(f, labels, presses)?,
+                Outcome::NonInteger(presses) => write_presses(f, labels, presses)?,
+                Outcome::Unreachable => {}
+                Outcome::Failed(error) => write!(f, "{error}")?,
+            }
+            writeln!(f)?;
+        }
+
+        let names = [
+            "solved",
+            "non-integer",
+            "exceeds limit",
+            "negative presses",
+            "unreachable",
+            "failed",
+        ];
+        let counts = names.map(|name| {
+            let count = self.0.iter().filter(|o| o.outcome.name() == name).count();
+            format!("{name} {count}")
+        });
+        writeln!(f, "{}", counts.join(", "))?;
+
+        let total = self
+            .0
+            .iter()
+            .try_fold(0i128, |total, outcome| match &outcome.outcome {
+                Outcome::Solved(solution) => total.checked_add(solution.cost),
+                _ => Some(total),
+            });
+        match total {
+            Some(total) => writeln!(f, "total {total}"),
+            None => writeln!(f, "total {}", SolveError::Overflow("total cost")),
+        }
+    }
+}
+
+fn write_presses<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    labels: impl Iterator<Item = char>,
+    presses: &[T],
+) -> fmt::Result {
+    let presses = labels
+        .zip(presses)
+        .map(|(label, presses)| format!("{label}={presses}"))
+        .collect::<Vec<_>>();
+    write!(f, "{}", presses.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::machine::tests::{button, machine};
+
+    fn example() -> Vec<Machine> {
+        vec![
+            machine((94, 34), (22, 67), (8400, 5400)),
+            machine((26, 66), (67, 21), (12748, 12176)),
+            machine((17, 86), (84, 37), (7870, 6450)),
+            machine((69, 23), (27, 71), (18641, 10279)),
+        ]
+    }
+
+    fn fraction(numerator: i128, denominator: i128) -> Fraction {
+        Fraction {
+            numerator,
+            denominator,
+        }
+    }
+
+    #[rstest]
+    #[case(6, 4, fraction(3, 2))]
+    #[case(6, -4, fraction(-3, 2))]
+    #[case(-8, -4, fraction(2, 1))]
+    #[case(0, -7, fraction(0, 1))]
+    fn test_fraction(
+        #[case] numerator: i128,
+        #[case] denominator: i128,
+        #[case] expected: Fraction,
+    ) {
+        assert_eq!(Ok(expected), Fraction::new(numerator, denominator));
+    }
+
+    #[test]
+    fn test_example_outcomes() {
+        let names = |outcomes: Vec<MachineOutcome>| {
+            outcomes
+                .iter()
+                .map(|outcome| outcome.outcome.name())
+                .collect::<Vec<_>>()
+        };
+        let part1 = outcomes(&example(), 0, Some(100));
+        assert_eq!(
+            Outcome::Solved(Solution {
+                presses: vec![80, 40],
+                cost: 280
+            }),
+            part1[0].outcome
+        );
+        assert_eq!(
+            vec!["solved", "non-integer", "solved", "non-integer"],
+            names(part1)
+        );
+        assert_eq!(
+            vec!["non-integer", "solved", "non-integer", "solved"],
+            names(outcomes(&example(), 10000000000000, None))
+        );
+    }
+
+    #[rstest]
+    #[case(machine((1, 0), (0, 1), (150, 20)), Outcome::ExceedsLimit(vec![150, 20]))]
+    #[case(machine((1, 0), (0, 1), (-5, 3)), Outcome::NegativePresses(vec![-5, 3]))]
+    #[case(machine((2, 0), (0, 3), (3, 4)), Outcome::NonInteger(vec![fraction(3, 2), fraction(4, 3)]))]
+    #[case(machine((1, 0), (1, 0), (250, 0)), Outcome::ExceedsLimit(vec![0, 250]))]
+    #[case(machine((1, 1), (2, 2), (5, 6)), Outcome::Unreachable)]
+    // collinear buttons whose steps never add up to the prize: B covers the most per token
+    #[case(machine((4, 0), (6, 0), (9, 0)), Outcome::NonInteger(vec![fraction(0, 1), fraction(3, 2)]))]
+    #[case(machine((0, 4), (0, -6), (0, 9)), Outcome::NonInteger(vec![fraction(9, 4), fraction(0, 1)]))]
+    #[case(machine((2, 2), (4, 4), (-3, -3)), Outcome::NonInteger(vec![fraction(-3, 2), fraction(0, 1)]))]
+    #[case(
+        machine((i128::MAX, 1), (1, i128::MAX), (1, 1)),
+        Outcome::Failed(SolveError::Overflow("determinant"))
+    )]
+    fn test_near_misses(#[case] machine: Machine, #[case] expected: Outcome) {
+        let outcomes = outcomes(&[machine], 0, Some(100));
+        assert_eq!(expected, outcomes[0].outcome);
+    }
+
+    #[test]
+    fn test_unlimited_retry_fails() {
+        // out of reach within 100 presses, and pressing the cheaper A all the way overflows
+        let mut machine = machine((1, 0), (1, 0), (i128::MAX / 2, 0));
+        machine.buttons[1].cost = 5;
+        let outcomes = outcomes(&[machine], 0, Some(100));
+        assert_eq!(
+            Outcome::Failed(SolveError::Overflow("cost")),
+            outcomes[0].outcome
+        );
+    }
+
+    #[test]
+    fn test_more_buttons_exceeding_limit() {
+        // A and B move as far as C in one press each, so both leave an odd C press to go
+        let machine = Machine {
+            buttons: vec![
+                button('A', (2, 0)),
+                button('B', (0, 2)),
+                button('C', (1, 1)),
+            ],
+            prize: (301, 301),
+        };
+        assert_eq!(
+            Ok(Outcome::ExceedsLimit(vec![0, 0, 301])),
+            Outcome::of(&machine, Some(100))
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut machines = example();
+        machines.push(machine((1, 0), (0, 1), (-5, 3)));
+        let outcomes = outcomes(&machines, 0, Some(100));
+        assert_eq!(
+            "machine  outcome           cost  presses
+      0  solved             280  A=80 B=40
+      1  non-integer          -  A=137021/969 B=131198/969
+      2  solved             200  A=38 B=86
+      3  non-integer          -  A=522989/2139 B=6098/93
+      4  negative presses     -  A=-5 B=3
+solved 2, non-integer 2, exceeds limit 0, negative presses 1, unreachable 0, failed 0
+total 480
+",
+            Summary(&outcomes).to_string()
+        );
+    }
+}